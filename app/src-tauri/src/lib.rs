@@ -132,6 +132,18 @@ fn set_storage(
 
 fn setup_app(app: &mut tauri::App) -> std::result::Result<(), Box<dyn std::error::Error>> {
     tauri::Manager::manage(app, std::sync::Mutex::new(AppData::new()));
+
+    let handler = app.handle().clone();
+    let watcher = todo_core::watch::watch(
+        std::time::Duration::from_millis(200),
+        move |events: Vec<todo_core::watch::ChangeEvent>| {
+            handler
+                .emit("tasks_changed", &events)
+                .unwrap_or_else(|e| eprintln!("error emitting tasks_changed: {}", e));
+        },
+    )?;
+    tauri::Manager::manage(app, watcher);
+
     let tray = tauri::tray::TrayIconBuilder::new();
     let tray = tray.icon(app.default_window_icon().ok_or("cannot find icon")?.clone());
 
@@ -203,6 +215,11 @@ fn setup_app(app: &mut tauri::App) -> std::result::Result<(), Box<dyn std::error
 fn init_database() -> Result<(), todo_core::db::DBError> {
     let conn = todo_core::db::create_connection()?;
     todo_core::db::ensure_table(&conn)?;
+    todo_core::backup::auto_backup(&conn, todo_core::backup::RotationPolicy::default())
+        .unwrap_or_else(|e| {
+            eprintln!("error taking backup: {}", e);
+            None
+        });
     Ok(())
 }
 
@@ -6,6 +6,7 @@ import TaskAdder from "./TaskAdder";
 import { error } from "./lib/notification";
 import { App, Divider, Layout } from "antd";
 import { logger } from "./lib/logger";
+import { listen } from "@tauri-apps/api/event";
 
 const MainPage: React.FC = () => {
   const appRef = App.useApp();
@@ -28,8 +29,10 @@ const MainPage: React.FC = () => {
 
   useEffect(() => {
     handleNotifyServer();
-    let handler = setInterval(handleNotifyServer, 1000);
-    return () => clearInterval(handler);
+    const unlisten = listen("tasks_changed", handleNotifyServer);
+    return () => {
+      unlisten.then((f) => f());
+    };
   }, []);
   if (tasks === undefined) {
     return <div>Loading...</div>;
@@ -1,5 +1,6 @@
 export interface Task {
   id: number;
+  uuid: string;
   create_time: string;
   task: string;
 }
@@ -0,0 +1,148 @@
+use clap::CommandFactory;
+
+use crate::interaction::{Cli, Shell};
+
+/// Subcommands whose positional arguments are task ids, and whether they
+/// take more than one.
+const TASK_COMMANDS: [(&str, bool); 5] = [
+    ("done", true),
+    ("del", true),
+    ("edit", false),
+    ("start", false),
+    ("link", false),
+];
+
+/// The completion script of `shell` for the binary called `bin`: the
+/// completions of clap, with task ids asked from `bin __complete`.
+pub fn script(shell: Shell, bin: &str) -> String {
+    let mut buf = Vec::new();
+    let generator = match shell {
+        Shell::Bash => clap_complete::Shell::Bash,
+        Shell::Zsh => clap_complete::Shell::Zsh,
+        Shell::Fish => clap_complete::Shell::Fish,
+    };
+    clap_complete::generate(generator, &mut Cli::command(), bin, &mut buf);
+    let mut script = String::from_utf8_lossy(&buf).into_owned();
+    match shell {
+        Shell::Bash => script.push_str(&BASH.replace("{bin}", bin)),
+        Shell::Zsh => {
+            // clap registers its own function last, ours wraps it
+            if let Some(index) = script.rfind("if [ \"$funcstack[1]\"") {
+                script.truncate(index);
+            }
+            script.push_str(&ZSH.replace("{bin}", bin));
+        }
+        Shell::Fish => script.push_str(&FISH.replace("{bin}", bin)),
+    }
+    script
+}
+
+const BASH: &str = r#"
+_{bin}_tasks() {
+    local tasks
+    mapfile -t tasks < <({bin} __complete -- "${COMP_WORDS[@]:1:COMP_CWORD}" 2>/dev/null)
+    if [[ ${#tasks[@]} -eq 0 ]]; then
+        _{bin} "$@"
+    elif [[ ${#tasks[@]} -eq 1 ]]; then
+        COMPREPLY=("${tasks[0]%%$'\t'*}")
+    else
+        # bash has no descriptions, show them after the ids
+        COMPREPLY=("${tasks[@]/$'\t'/  -- }")
+    fi
+}
+
+complete -F _{bin}_tasks -o nosort -o bashdefault -o default {bin}
+"#;
+
+const ZSH: &str = r#"_{bin}_tasks() {
+    local -a tasks
+    tasks=(${(f)"$({bin} __complete -- "${(@)words[2,CURRENT]}" 2>/dev/null)"})
+    if (( ${#tasks} )); then
+        tasks=("${(@)tasks/$'\t'/:}")
+        _describe -t tasks 'task' tasks
+    else
+        _{bin} "$@"
+    fi
+}
+
+if [ "$funcstack[1]" = "_{bin}" ]; then
+    _{bin}_tasks "$@"
+else
+    compdef _{bin}_tasks {bin}
+fi
+"#;
+
+const FISH: &str = r#"
+function __{bin}_tasks
+    {bin} __complete -- (commandline -opc)[2..-1] (commandline -ct) 2>/dev/null
+end
+
+complete -c {bin} -f -n '__{bin}_tasks | string length -q' -a '(__{bin}_tasks)'
+"#;
+
+/// Returns the typed prefix when the last of the command line `words`,
+/// without the binary, is a task id.
+pub fn task_prefix(words: &[String]) -> Option<&str> {
+    let (current, before) = words.split_last()?;
+    if current.starts_with('-') {
+        return None;
+    }
+    let command = Cli::command();
+    // options like `--output json` take the next word as their value
+    let takes_value = |long: &str| {
+        std::iter::once(&command)
+            .chain(command.get_subcommands())
+            .flat_map(|command| command.get_arguments())
+            .any(|arg| arg.get_long() == Some(long) && arg.get_action().takes_values())
+    };
+    let mut positionals = Vec::new();
+    let mut words = before.iter();
+    while let Some(word) = words.next() {
+        match word.strip_prefix("--") {
+            Some(long) if !long.contains('=') && takes_value(long) => {
+                words.next();
+            }
+            Some(_) => {}
+            None if word.starts_with('-') => {}
+            None => positionals.push(word.as_str()),
+        }
+    }
+    let (name, args) = positionals.split_first()?;
+    let (_, many) = TASK_COMMANDS.iter().find(|(command, _)| command == name)?;
+    (*many || args.is_empty()).then_some(current.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a trailing space completes a new, empty word
+    fn prefix(line: &str) -> Option<String> {
+        let words: Vec<String> = line.split(' ').map(str::to_owned).collect();
+        task_prefix(&words).map(str::to_owned)
+    }
+
+    #[test]
+    fn test_task_prefix() {
+        assert_eq!(prefix("done "), Some(String::new()));
+        assert_eq!(prefix("done 3 1"), Some("1".to_owned()));
+        assert_eq!(prefix("--output json del 1"), Some("1".to_owned()));
+        assert_eq!(prefix("--global edit 4"), Some("4".to_owned()));
+        assert_eq!(prefix("edit 4 "), None);
+        assert_eq!(prefix("link 4 --kind url "), None);
+        assert_eq!(prefix("done --"), None);
+        assert_eq!(prefix("add "), None);
+        assert_eq!(prefix("don"), None);
+    }
+
+    #[test]
+    fn test_script() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = script(shell, "todo");
+            assert!(script.contains("todo __complete --"));
+        }
+        let zsh = script(Shell::Zsh, "todo");
+        assert!(zsh.contains("compdef _todo_tasks todo"));
+        assert!(!zsh.contains("compdef _todo todo"));
+    }
+}
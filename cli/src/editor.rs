@@ -0,0 +1,203 @@
+use snafu::{ResultExt, prelude::Snafu};
+use todo_core::db;
+
+#[derive(Debug, Snafu)]
+pub enum EditorError {
+    #[snafu(display("failed to access '{}'", path.display()))]
+    Io {
+        source: std::io::Error,
+        path: std::path::PathBuf,
+    },
+    #[snafu(display("failed to run editor '{}'", editor))]
+    Spawn {
+        source: std::io::Error,
+        editor: String,
+    },
+    #[snafu(display("editor '{}' exited with {}", editor, status))]
+    Status {
+        editor: String,
+        status: std::process::ExitStatus,
+    },
+    #[snafu(display("line {}: {}", line, reason))]
+    Parse { line: usize, reason: String },
+}
+
+type Result<T> = std::result::Result<T, EditorError>;
+
+/// `$VISUAL`, then `$EDITOR`, then a platform default.
+fn editor_command() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| match cfg!(windows) {
+            true => "notepad".to_owned(),
+            false => "vi".to_owned(),
+        })
+}
+
+/// Opens `content` in the editor of the user and returns the saved content.
+pub fn edit(content: &str) -> Result<String> {
+    // a new file with a random name, so that nothing planted in the shared
+    // temp dir is followed
+    let mut file = tempfile::Builder::new()
+        .prefix("todo-edit-")
+        .suffix(".txt")
+        .tempfile()
+        .context(IoSnafu {
+            path: std::env::temp_dir(),
+        })?;
+    let path = file.path().to_owned();
+    std::io::Write::write_all(&mut file, content.as_bytes()).context(IoSnafu { path: &path })?;
+    // the editor may carry arguments, like `code --wait`
+    let editor = editor_command();
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+        .context(SpawnSnafu { editor: &editor });
+    let content = status.and_then(|status| match status.success() {
+        true => std::fs::read_to_string(&path).context(IoSnafu { path: &path }),
+        false => StatusSnafu { editor, status }.fail(),
+    });
+    // removes the file
+    drop(file);
+    content
+}
+
+const BULK_HEADER: &str = "\
+# One open task per line. Edit the text after [id] to edit a task, remove
+# the line to delete it, and add lines without [id] to add tasks. A '\\n'
+# in the text is a line break. Lines starting with '#' are ignored.
+";
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                result.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                result.push('\\');
+                chars.next();
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Renders the tasks for `todo edit --all`, one `[id] text` line per task.
+pub fn render_bulk(tasks: &[db::OpenTask]) -> String {
+    let lines: String = tasks
+        .iter()
+        .map(|task| format!("[{}] {}\n", task.id, escape(&task.task)))
+        .collect();
+    format!("{}\n{}", BULK_HEADER, lines)
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BulkEdit {
+    pub added: Vec<String>,
+    /// id and new text of the changed tasks
+    pub edited: Vec<(i64, String)>,
+    pub deleted: Vec<i64>,
+}
+
+/// Compares the edited buffer with the `tasks` it was rendered from.
+pub fn parse_bulk(content: &str, tasks: &[db::OpenTask]) -> Result<BulkEdit> {
+    let mut edit = BulkEdit::default();
+    let mut seen = std::collections::BTreeSet::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parsed = line.strip_prefix('[').and_then(|rest| {
+            let (id, text) = rest.split_once(']')?;
+            Some((id.trim().parse::<i64>().ok()?, text.trim()))
+        });
+        let Some((id, text)) = parsed else {
+            edit.added.push(unescape(line));
+            continue;
+        };
+        let reason = match tasks.iter().find(|task| task.id == id) {
+            None => format!("task [{}] is not one of the open tasks", id),
+            Some(_) if !seen.insert(id) => format!("task [{}] appears twice", id),
+            // a task whose text was cleared is deleted like a removed line
+            Some(_) if text.is_empty() => {
+                edit.deleted.push(id);
+                continue;
+            }
+            Some(task) => {
+                let text = unescape(text);
+                if text != task.task {
+                    edit.edited.push((id, text));
+                }
+                continue;
+            }
+        };
+        return ParseSnafu {
+            line: index + 1,
+            reason,
+        }
+        .fail();
+    }
+    edit.deleted = tasks
+        .iter()
+        .filter(|task| !seen.contains(&task.id) || edit.deleted.contains(&task.id))
+        .map(|task| task.id)
+        .collect();
+    Ok(edit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: i64, text: &str) -> db::OpenTask {
+        db::OpenTask {
+            id,
+            uuid: format!("uuid-{}", id),
+            create_time: "2025-01-01 00:00:00".to_owned(),
+            task: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_parse_bulk() {
+        let tasks = vec![
+            task(1, "write docs"),
+            task(2, "fix parser\nand tests"),
+            task(3, "ship"),
+        ];
+        let content = render_bulk(&tasks);
+        assert_eq!(parse_bulk(&content, &tasks).unwrap(), BulkEdit::default());
+
+        let content = content
+            .replace("[1] write docs", "[1] write the docs")
+            .replace("[3] ship\n", "[3]\nbuy milk\n");
+        assert!(content.contains("[2] fix parser\\nand tests"));
+        assert_eq!(
+            parse_bulk(&content, &tasks).unwrap(),
+            BulkEdit {
+                added: vec!["buy milk".to_owned()],
+                edited: vec![(1, "write the docs".to_owned())],
+                deleted: vec![3],
+            }
+        );
+
+        let error = parse_bulk("[2] a\n[2] b\n", &tasks).unwrap_err();
+        assert!(matches!(error, EditorError::Parse { line: 2, .. }));
+        assert!(parse_bulk("[9] unknown\n", &tasks).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+
+use snafu::{ResultExt, prelude::Snafu};
+
+#[derive(Debug, Snafu)]
+pub enum GitError {
+    #[snafu(display("failed to run 'git {}'", args))]
+    Spawn {
+        source: std::io::Error,
+        args: String,
+    },
+    #[snafu(display("'git {}' failed: {}", args, stderr))]
+    Failed { args: String, stderr: String },
+    #[snafu(display("failed to write hook '{}'", path.display()))]
+    Hook {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+    #[snafu(display("'{}' was not installed by todo, use --force to replace it", path.display()))]
+    ForeignHook { path: PathBuf },
+}
+
+type Result<T> = std::result::Result<T, GitError>;
+
+/// Marks hooks written by `todo git-hook install` so that reinstalling
+/// replaces them but hooks of other tools are kept.
+const HOOK_MARKER: &str = "# installed by todo git-hook";
+
+/// Runs git in the working directory and returns its trimmed stdout.
+fn git(args: &[&str]) -> Result<String> {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .output()
+        .context(SpawnSnafu {
+            args: args.join(" "),
+        })?;
+    if !output.status.success() {
+        return FailedSnafu {
+            args: args.join(" "),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        }
+        .fail();
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Checks out `branch`, creating it from HEAD when it does not exist yet.
+/// Returns whether the branch was created.
+pub fn checkout(branch: &str) -> Result<bool> {
+    let reference = format!("refs/heads/{}", branch);
+    let exists = git(&["rev-parse", "--verify", "--quiet", &reference]).is_ok();
+    match exists {
+        true => git(&["checkout", branch])?,
+        false => git(&["checkout", "-b", branch])?,
+    };
+    Ok(!exists)
+}
+
+/// Returns the SHA and the message of the last commit.
+pub fn head_commit() -> Result<(String, String)> {
+    let output = git(&["log", "-1", "--format=%H%n%B"])?;
+    let (sha, message) = output.split_once('\n').unwrap_or((&output, ""));
+    Ok((sha.to_owned(), message.to_owned()))
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Writes the `commit-msg` hook, which warns about references to unknown
+/// tasks, and the `post-commit` hook, which links the commit to the
+/// referenced tasks and, with `close`, closes the ones after a closing
+/// keyword.
+pub fn install_hooks(exe: &str, close: bool, force: bool) -> Result<Vec<PathBuf>> {
+    let dir = PathBuf::from(git(&["rev-parse", "--git-path", "hooks"])?);
+    std::fs::create_dir_all(&dir).context(HookSnafu { path: &dir })?;
+    let exe = shell_quote(exe);
+    let post_commit = match close {
+        true => format!("exec {} git-hook post-commit --close", exe),
+        false => format!("exec {} git-hook post-commit", exe),
+    };
+    let hooks = [
+        (
+            "commit-msg",
+            format!("exec {} git-hook commit-msg \"$1\"", exe),
+        ),
+        ("post-commit", post_commit),
+    ];
+    let mut paths = Vec::new();
+    for (name, command) in hooks {
+        let path = dir.join(name);
+        let foreign =
+            std::fs::read_to_string(&path).is_ok_and(|content| !content.contains(HOOK_MARKER));
+        if foreign && !force {
+            return ForeignHookSnafu { path }.fail();
+        }
+        let content = format!("#!/bin/sh\n{}\n{}\n", HOOK_MARKER, command);
+        std::fs::write(&path, content).context(HookSnafu { path: &path })?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+                .context(HookSnafu { path: &path })?;
+        }
+        paths.push(path);
+    }
+    Ok(paths)
+}
@@ -1,6 +1,6 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use crossterm::{self, execute};
-use snafu::{prelude::Snafu, ResultExt};
+use snafu::{ResultExt, prelude::Snafu};
 
 #[derive(Debug, Snafu)]
 pub enum InteractionError {
@@ -9,8 +9,6 @@ pub enum InteractionError {
         source: std::io::Error,
         operator: &'static str,
     },
-    #[snafu(display("mpsc communication failed"))]
-    RecvEvent { source: std::sync::mpsc::RecvError },
 }
 
 type Result<T> = std::result::Result<T, InteractionError>;
@@ -18,169 +16,802 @@ type Result<T> = std::result::Result<T, InteractionError>;
 #[derive(Debug, Parser)]
 #[command(propagate_version = true)]
 #[command(version, about = "todo command line tools")]
+#[command(after_help = "\
+Environment:
+  TODO_PASSPHRASE  passphrase of an encrypted database
+  TODO_KEYFILE     file containing the passphrase
+  TODO_NO_HISTORY  set to keep prompt input out of the input history, which is
+                   never kept for an encrypted database")]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// ignore the git repository of the working directory: list and select
+    /// the tasks of every repository, add tasks to none
+    #[arg(long, global = true)]
+    pub global: bool,
+    /// json prints one array, jsonl one object per line
+    #[arg(long, global = true, value_enum, default_value_t = Output::Plain)]
+    pub output: Output,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum Commands {
-    Add {},
-    Del {},
-    Edit {},
-    Done {},
+    Add {
+        /// task text, read one task per line from piped stdin or prompted
+        /// when omitted
+        text: Vec<String>,
+    },
+    Del {
+        /// task ids or uuid (prefixes), read from piped stdin or selected
+        /// interactively when omitted
+        tasks: Vec<String>,
+    },
+    Edit {
+        /// task id or uuid (prefix), select interactively when omitted
+        task: Option<String>,
+        /// new task text, read from piped stdin or prompted when omitted
+        text: Vec<String>,
+        /// edit the text in $VISUAL or $EDITOR
+        #[arg(long, conflicts_with = "text")]
+        editor: bool,
+        /// edit every open task in $VISUAL or $EDITOR, one line per task
+        #[arg(long, conflicts_with_all = ["task", "text", "editor"])]
+        all: bool,
+    },
+    Done {
+        /// task ids or uuid (prefixes), read from piped stdin or selected
+        /// interactively when omitted
+        tasks: Vec<String>,
+    },
     List {
         #[arg(long)]
         all: bool,
     },
     Clean {},
+    /// merge tasks with another todo database, e.g. one in a shared folder,
+    /// or with a todo sync server given as 'http://host:port'
+    Sync {
+        path: String,
+        /// create the database file when it does not exist
+        #[arg(long)]
+        create: bool,
+    },
+    /// encrypt the task database, requires the 'encryption' feature
+    Encrypt {
+        /// file containing the passphrase, defaults to TODO_PASSPHRASE, the
+        /// configured keyfile or a prompt
+        #[arg(long)]
+        keyfile: Option<String>,
+    },
+    /// decrypt the task database back to plaintext
+    Decrypt {
+        /// file containing the passphrase, defaults to TODO_PASSPHRASE, the
+        /// configured keyfile or a prompt
+        #[arg(long)]
+        keyfile: Option<String>,
+    },
+    /// take a snapshot of the task database
+    Backup {
+        #[command(subcommand)]
+        command: Option<BackupCommands>,
+        /// number of days for which the newest snapshot is kept
+        #[arg(long, default_value_t = 7)]
+        daily: usize,
+        /// number of weeks for which the newest snapshot is kept
+        #[arg(long, default_value_t = 4)]
+        weekly: usize,
+    },
+    /// write all tasks, including closed and deleted ones, to a file
+    Export {
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        /// output file, stdout when omitted
+        #[arg(long, short = 'o')]
+        file: Option<String>,
+        /// headings of markdown checklists
+        #[arg(long, value_enum, default_value_t = Group::Status)]
+        group: Group,
+        /// comma separated csv columns, defaults to id,status,create_time,finished_time,task
+        #[arg(long, value_enum, value_delimiter = ',')]
+        columns: Vec<Column>,
+        /// only tasks created or finished on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+        /// only tasks created or finished on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<String>,
+    },
+    /// import TODO, FIXME and HACK comments below a directory as tasks,
+    /// closing the tasks whose comment is gone
+    Scan {
+        /// directory to scan, files ignored by .gitignore are skipped
+        #[arg(default_value = ".")]
+        path: String,
+    },
+    /// read tasks written by 'todo export'
+    Import {
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        /// merge adds the missing tasks, replace makes the database match the file
+        #[arg(long, value_enum, default_value_t = ImportMode::Merge)]
+        mode: ImportMode,
+        /// input file, '-' for stdin
+        path: String,
+    },
+    /// check out the branch of a task, creating it when needed
+    Start {
+        /// task id or uuid (prefix), select interactively when omitted
+        task: Option<String>,
+    },
+    /// link a branch, commit or url to a task, list its links without a target
+    Link {
+        /// task id or uuid (prefix)
+        task: String,
+        target: Option<String>,
+        /// guessed from the target when omitted
+        #[arg(long, value_enum)]
+        kind: Option<LinkKind>,
+        /// remove the link to the target instead
+        #[arg(long, requires = "target")]
+        remove: bool,
+    },
+    /// full-screen interface to browse, add, edit, close and delete tasks
+    Tui {},
+    /// created and completed tasks per day and week, time to close and streaks
+    Stats {
+        /// days of the daily charts
+        #[arg(long, default_value_t = 14)]
+        days: usize,
+        /// weeks of the weekly table
+        #[arg(long, default_value_t = 8)]
+        weeks: usize,
+    },
+    /// link commits mentioning todo#<id> to the task
+    GitHook {
+        #[command(subcommand)]
+        command: GitHookCommands,
+    },
+    /// print the completion script of a shell, e.g. 'source <(todo completions bash)'
+    Completions {
+        shell: Shell,
+    },
+    /// print the task ids completing a command line, used by the completion scripts
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        /// the words after the binary name, the last one being completed
+        #[arg(raw = true)]
+        words: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Output {
+    Plain,
+    Table,
+    Json,
+    Jsonl,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    Json,
+    Todotxt,
+    Ics,
+    Md,
+    /// export only
+    Csv,
+    /// import only, reads the output of 'task export'
+    Taskwarrior,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum Column {
+    Id,
+    Uuid,
+    Status,
+    CreateTime,
+    FinishedTime,
+    DueTime,
+    Task,
+    Priority,
+    Project,
+    Tags,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Group {
+    Status,
+    Project,
+    Tag,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ImportMode {
+    Merge,
+    Replace,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum LinkKind {
+    Branch,
+    Commit,
+    Url,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum GitHookCommands {
+    /// write the commit-msg and post-commit hooks of the current repository
+    Install {
+        /// close the tasks of 'closes todo#<id>', 'fixes todo#<id>' and alike
+        #[arg(long)]
+        close: bool,
+        /// replace hooks which were not installed by todo
+        #[arg(long)]
+        force: bool,
+    },
+    /// run by the commit-msg hook
+    #[command(hide = true)]
+    CommitMsg { file: String },
+    /// run by the post-commit hook
+    #[command(hide = true)]
+    PostCommit {
+        #[arg(long)]
+        close: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BackupCommands {
+    /// list snapshots with their time and task counts
+    List {},
+    /// replace the task database with a snapshot
+    Restore { snapshot: String },
+}
+
+/// Completes the word under the cursor with tags and projects, or the whole
+/// line with the text of existing tasks.
+#[derive(Default)]
+pub struct TaskCompleter {
+    pub labels: Vec<String>,
+    pub texts: Vec<String>,
+}
+
+impl TaskCompleter {
+    fn candidates(&self, line: &str) -> (usize, Vec<String>) {
+        let start = line
+            .char_indices()
+            .rfind(|(_, c)| c.is_whitespace())
+            .map_or(0, |(index, c)| index + c.len_utf8());
+        let word = &line[start..];
+        let labels: Vec<String> = self
+            .labels
+            .iter()
+            .filter(|label| !word.is_empty() && label.starts_with(word) && *label != word)
+            .cloned()
+            .collect();
+        if !labels.is_empty() {
+            return (start, labels);
+        }
+        let lower = line.to_lowercase();
+        let texts = self
+            .texts
+            .iter()
+            .filter(|text| !line.is_empty() && text.to_lowercase().starts_with(&lower))
+            .filter(|text| text.len() > line.len())
+            .cloned()
+            .collect();
+        (0, texts)
+    }
+}
+
+impl rustyline::completion::Completer for TaskCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(&line[..pos]))
+    }
+}
+
+impl rustyline::hint::Hinter for TaskCompleter {
+    type Hint = String;
+}
+
+impl rustyline::highlight::Highlighter for TaskCompleter {}
+
+impl rustyline::validate::Validator for TaskCompleter {}
+
+impl rustyline::Helper for TaskCompleter {}
+
+type LineEditor = rustyline::Editor<TaskCompleter, rustyline::history::FileHistory>;
+
+/// Set to any value to keep the prompt input out of the history.
+pub const NO_HISTORY_ENV: &str = "TODO_NO_HISTORY";
+
+/// Input history, shared by the prompts of all commands. `None` when the user
+/// opted out, or when the database is encrypted, since the history would keep
+/// its task texts in plaintext.
+fn history_path() -> Option<std::path::PathBuf> {
+    let opted_out = std::env::var_os(NO_HISTORY_ENV).is_some();
+    // an unreadable keyfile still means that the database is encrypted
+    let encrypted = !matches!(todo_core::encryption::configured_key(), Ok(None));
+    let folder = todo_core::root_path::get_folder().ok()?;
+    history_file(&folder, opted_out, encrypted)
+}
+
+fn history_file(folder: &str, opted_out: bool, encrypted: bool) -> Option<std::path::PathBuf> {
+    match opted_out || encrypted {
+        true => None,
+        false => Some(std::path::Path::new(folder).join("input_history.txt")),
+    }
+}
+
+fn line_editor(completer: TaskCompleter) -> LineEditor {
+    let config = rustyline::Config::builder()
+        .completion_type(rustyline::CompletionType::List)
+        .auto_add_history(false)
+        .build();
+    let mut editor: LineEditor = rustyline::Editor::with_config(config).unwrap();
+    editor.set_helper(Some(completer));
+    if let Some(path) = history_path() {
+        // there is no history before the first input
+        let _ = editor.load_history(&path);
+    }
+    editor
+}
+
+/// Reads lines while they end with a backslash, which continues the input on
+/// the next line. Saves the input to the history.
+fn read_line_impl(editor: &mut LineEditor, prompt: &str, initial: &str) -> String {
+    let continued = format!("{}> ", " ".repeat(prompt.chars().count().saturating_sub(2)));
+    let mut input = String::new();
+    let mut line = editor.readline_with_initial(prompt, (initial, ""));
+    loop {
+        match line {
+            Ok(text) => match text.strip_suffix('\\') {
+                Some(text) => {
+                    input.push_str(text);
+                    input.push('\n');
+                }
+                None => {
+                    input.push_str(&text);
+                    break;
+                }
+            },
+            Err(_) => return String::new(),
+        }
+        line = editor.readline(&continued);
+    }
+    if !input.trim().is_empty()
+        && editor.add_history_entry(input.as_str()).unwrap_or(false)
+        && let Some(path) = history_path()
+        && let Err(e) = editor.append_history(&path)
+    {
+        eprintln!("Warning: failed to save the input history: {}", e);
+    }
+    input
 }
 
-type LineEditor = rustyline::Editor<(), rustyline::history::FileHistory>;
+pub fn read_input(content: &str, completer: TaskCompleter) -> String {
+    let mut editor = line_editor(completer);
+    read_line_impl(&mut editor, &(content.to_owned() + ">> "), "")
+}
 
-fn read_line_impl(editor: &mut LineEditor, prompt: &str) -> String {
-    let readline = editor.readline(prompt);
-    match readline {
-        Ok(line) => {
-            if line.is_empty() {
-                line
-            } else {
-                line + &read_line_impl(editor, prompt)
+pub fn read_input_with_default(content: &str, default: &str, completer: TaskCompleter) -> String {
+    let mut editor = line_editor(completer);
+    read_line_impl(&mut editor, &(content.to_owned() + ">> "), default)
+}
+
+/// Reads a single line which is neither completed nor kept in the history.
+pub fn read_secret(content: &str) -> String {
+    let mut editor = rustyline::DefaultEditor::new().unwrap();
+    editor
+        .readline(&(content.to_owned() + ">> "))
+        .unwrap_or_default()
+}
+
+/// Matches the characters of `query` in order, ignoring case. Returns the
+/// score, higher is better, and the positions of the matched characters.
+fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let lower = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let query: Vec<char> = query.chars().map(lower).collect();
+    let text: Vec<char> = text.chars().map(lower).collect();
+    let Some(first) = query.first() else {
+        return Some((0, Vec::new()));
+    };
+    let mut best: Option<(i64, Vec<usize>)> = None;
+    // the rest is matched as early as possible after every occurrence of the
+    // first character, the best scoring start wins
+    for start in (0..text.len()).filter(|index| text[*index] == *first) {
+        let mut positions = vec![start];
+        for c in &query[1..] {
+            let from = positions[positions.len() - 1] + 1;
+            match text[from..].iter().position(|t| t == c) {
+                Some(offset) => positions.push(from + offset),
+                None => break,
             }
         }
-        Err(_) => String::new(),
+        if positions.len() < query.len() {
+            // a later start cannot match either
+            break;
+        }
+        let mut score = 0;
+        for (index, position) in positions.iter().enumerate() {
+            let word_start = *position == 0 || !text[position - 1].is_alphanumeric();
+            score += match index.checked_sub(1).map(|previous| positions[previous]) {
+                Some(previous) if previous + 1 == *position => 6,
+                Some(previous) => 1 - (position - previous - 1).min(5) as i64,
+                None => 1,
+            };
+            if word_start {
+                score += 3;
+            }
+        }
+        if best.as_ref().is_none_or(|(best, _)| score > *best) {
+            best = Some((score, positions));
+        }
     }
+    best
 }
 
-pub fn read_input(content: &str) -> String {
-    let mut editor: LineEditor = rustyline::DefaultEditor::new().unwrap();
-    read_line_impl(&mut editor, &(content.to_owned() + ">> "))
+/// Restores the terminal when the picker returns, also on errors.
+pub(crate) struct RawScreen;
+
+impl RawScreen {
+    pub(crate) fn enter(stdout: &mut std::io::Stdout) -> Result<Self> {
+        crossterm::terminal::enable_raw_mode().context(TerminalSnafu {
+            operator: "enable_raw_mode",
+        })?;
+        execute!(
+            stdout,
+            crossterm::terminal::EnterAlternateScreen,
+            crossterm::cursor::Hide
+        )
+        .context(TerminalSnafu {
+            operator: "enter alternate screen",
+        })?;
+        Ok(RawScreen)
+    }
 }
 
-pub fn read_input_with_default(content: &str, default: &str) -> String {
-    let mut editor: LineEditor = rustyline::DefaultEditor::new().unwrap();
-    let prompt: String = content.to_owned() + ">> ";
-    match editor.readline_with_initial(&prompt, (default, "")) {
-        Ok(line) => {
-            if line.is_empty() {
-                String::new()
-            } else {
-                line + &read_line_impl(&mut editor, &prompt)
+impl Drop for RawScreen {
+    fn drop(&mut self) {
+        let _ = execute!(
+            std::io::stdout(),
+            crossterm::terminal::LeaveAlternateScreen,
+            crossterm::cursor::Show,
+        );
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+}
+
+struct Picker<'a> {
+    options: &'a [&'a str],
+    query: String,
+    /// index of the option and positions of the matched characters, best
+    /// match first
+    matches: Vec<(usize, Vec<usize>)>,
+    /// index into `matches`
+    selected: usize,
+    /// first match shown in the viewport
+    offset: usize,
+    /// options toggled in multi-select mode, `None` when picking one option
+    chosen: Option<Vec<bool>>,
+    /// whether keys edit the query, always in single mode and after `/` in
+    /// multi-select mode where space and `a` are taken
+    editing: bool,
+}
+
+impl<'a> Picker<'a> {
+    fn new(options: &'a [&'a str], multiple: bool) -> Self {
+        let mut picker = Picker {
+            options,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            offset: 0,
+            chosen: multiple.then(|| vec![false; options.len()]),
+            editing: !multiple,
+        };
+        picker.filter();
+        picker
+    }
+
+    fn filter(&mut self) {
+        let mut matches: Vec<(i64, usize, Vec<usize>)> = self
+            .options
+            .iter()
+            .enumerate()
+            .filter_map(|(index, option)| {
+                let (score, positions) = fuzzy_match(&self.query, option)?;
+                Some((score, index, positions))
+            })
+            .collect();
+        // stable, so equal scores keep the order of the options
+        matches.sort_by_key(|(score, _, _)| std::cmp::Reverse(*score));
+        self.matches = matches
+            .into_iter()
+            .map(|(_, index, positions)| (index, positions))
+            .collect();
+        self.selected = 0;
+        self.offset = 0;
+    }
+
+    /// Moves the selection by `delta` rows, clamped to the matches.
+    fn move_by(&mut self, delta: isize) {
+        let last = self.matches.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    fn toggle(&mut self) {
+        if let (Some(chosen), Some((index, _))) =
+            (&mut self.chosen, self.matches.get(self.selected))
+        {
+            chosen[*index] = !chosen[*index];
+        }
+    }
+
+    /// Chooses every match, or none of them when all are already chosen.
+    fn toggle_all(&mut self) {
+        if let Some(chosen) = &mut self.chosen {
+            let all = self.matches.iter().all(|(index, _)| chosen[*index]);
+            for (index, _) in &self.matches {
+                chosen[*index] = !all;
             }
         }
-        Err(_) => String::new(),
-    }
-}
-
-fn render_menu(
-    stdout: &mut std::io::Stdout,
-    options: &[&str],
-    selected_index: usize,
-) -> Result<()> {
-    execute!(
-        stdout,
-        crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
-    )
-    .context(TerminalSnafu {
-        operator: "clear screen",
-    })?;
-    for (i, option) in options.iter().enumerate() {
-        if i == selected_index {
-            execute!(
-                stdout,
-                crossterm::cursor::MoveTo(0, i as u16),
-                crossterm::style::SetForegroundColor(crossterm::style::Color::Blue),
-                crossterm::style::Print(format!("> {}\n", *option)),
-                crossterm::style::ResetColor,
-            )
-            .context(TerminalSnafu {
-                operator: "render menu",
-            })?;
-        } else {
-            execute!(
+    }
+
+    /// Options confirmed by Enter, the selected one when none were toggled.
+    fn confirm(&self) -> Vec<usize> {
+        let chosen: Vec<usize> = match &self.chosen {
+            Some(chosen) => (0..chosen.len()).filter(|index| chosen[*index]).collect(),
+            None => Vec::new(),
+        };
+        match chosen.is_empty() {
+            true => self
+                .matches
+                .get(self.selected)
+                .map(|(index, _)| *index)
+                .into_iter()
+                .collect(),
+            false => chosen,
+        }
+    }
+
+    /// Number of option rows between the query and the status line.
+    fn page_size(height: u16) -> usize {
+        (height as usize).saturating_sub(2).max(1)
+    }
+
+    fn render(&mut self, stdout: &mut std::io::Stdout) -> Result<()> {
+        use crossterm::style::{Attribute, Color, Print, SetAttribute, SetForegroundColor};
+        let (width, height) = crossterm::terminal::size().context(TerminalSnafu {
+            operator: "get terminal size",
+        })?;
+        let page = Self::page_size(height);
+        // scroll just enough to keep the selection visible
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + page {
+            self.offset = self.selected + 1 - page;
+        }
+        let operator = "render menu";
+        let query = match (self.editing, self.chosen.is_some()) {
+            (false, true) if self.query.is_empty() => "press / to filter".to_owned(),
+            (true, true) => format!("{}_", self.query),
+            _ => self.query.clone(),
+        };
+        crossterm::queue!(
+            stdout,
+            crossterm::terminal::Clear(crossterm::terminal::ClearType::All),
+            crossterm::cursor::MoveTo(0, 0),
+            Print(format!("filter: {}", query)),
+        )
+        .context(TerminalSnafu { operator })?;
+        let visible = self.matches.iter().enumerate().skip(self.offset).take(page);
+        for (row, (index, (option, positions))) in visible.enumerate() {
+            let (cursor, color) = match index == self.selected {
+                true => ("> ", Color::Blue),
+                false => ("  ", Color::Reset),
+            };
+            let checkbox = match &self.chosen {
+                Some(chosen) if chosen[*option] => "[x] ",
+                Some(_) => "[ ] ",
+                None => "",
+            };
+            let prefix = format!("{}{}", cursor, checkbox);
+            crossterm::queue!(
                 stdout,
-                crossterm::cursor::MoveTo(0, i as u16),
-                crossterm::style::Print(format!("  {}\n", *option)),
+                crossterm::cursor::MoveTo(0, row as u16 + 1),
+                SetForegroundColor(color),
+                Print(&prefix),
             )
-            .context(TerminalSnafu {
-                operator: "render menu",
-            })?;
+            .context(TerminalSnafu { operator })?;
+            // only the first line of a task fits in a row
+            let text = self.options[*option].lines().next().unwrap_or_default();
+            let room = (width as usize).saturating_sub(prefix.len());
+            for (position, c) in text.chars().take(room).enumerate() {
+                match positions.contains(&position) {
+                    true => crossterm::queue!(
+                        stdout,
+                        SetForegroundColor(Color::Yellow),
+                        SetAttribute(Attribute::Bold),
+                        Print(c),
+                        SetAttribute(Attribute::Reset),
+                        SetForegroundColor(color),
+                    ),
+                    false => crossterm::queue!(stdout, Print(c)),
+                }
+                .context(TerminalSnafu { operator })?;
+            }
+            crossterm::queue!(stdout, crossterm::style::ResetColor)
+                .context(TerminalSnafu { operator })?;
         }
+        let status = match &self.chosen {
+            Some(chosen) => format!(
+                "{}/{}, {} chosen  space to toggle, a to toggle all, / to filter, enter to confirm, esc to cancel",
+                self.matches.len(),
+                self.options.len(),
+                chosen.iter().filter(|chosen| **chosen).count()
+            ),
+            None => format!(
+                "{}/{}  type to filter, up/down/pgup/pgdn/home/end to move, enter to select, esc to cancel",
+                self.matches.len(),
+                self.options.len()
+            ),
+        };
+        crossterm::queue!(
+            stdout,
+            crossterm::cursor::MoveTo(0, height.saturating_sub(1)),
+            SetAttribute(Attribute::Dim),
+            Print(status.chars().take(width as usize).collect::<String>()),
+            SetAttribute(Attribute::Reset),
+        )
+        .context(TerminalSnafu { operator })?;
+        std::io::Write::flush(stdout).context(TerminalSnafu { operator })
     }
-    execute!(stdout, crossterm::cursor::MoveTo(0, options.len() as u16)).context(
-        TerminalSnafu {
-            operator: "reset cursor",
-        },
-    )?;
-    Ok(())
 }
 
-pub fn select(options: &[&str]) -> Result<usize> {
-    assert!(!options.is_empty());
-    crossterm::terminal::enable_raw_mode().context(TerminalSnafu {
-        operator: "enable_raw_mode",
-    })?;
+fn pick(options: &[&str], multiple: bool) -> Result<Option<Vec<usize>>> {
+    use crossterm::event::{Event, KeyCode, KeyModifiers};
     let mut stdout = std::io::stdout();
-    execute!(
-        stdout,
-        crossterm::terminal::EnterAlternateScreen,
-        crossterm::cursor::Hide
-    )
-    .context(TerminalSnafu {
-        operator: "enter alternate screen",
-    })?;
-    let mut selected_index = 0;
-    render_menu(&mut stdout, &options, selected_index)?;
-
-    let (tx, rx) = std::sync::mpsc::channel();
-    std::thread::spawn(move || loop {
-        if let Ok(event) = crossterm::event::read() {
-            match tx.send(event) {
-                Ok(_) => {}
-                Err(_) => break,
-            }
-        }
-    });
+    let _screen = RawScreen::enter(&mut stdout)?;
+    let mut picker = Picker::new(options, multiple);
+    picker.render(&mut stdout)?;
     loop {
-        match rx.recv().context(RecvEventSnafu {})? {
-            crossterm::event::Event::Key(key_event) => {
-                if key_event.is_press() {
-                    match key_event.code {
-                        crossterm::event::KeyCode::Up => {
-                            if selected_index > 0 {
-                                selected_index -= 1;
-                            }
-                            render_menu(&mut stdout, &options, selected_index)?;
-                        }
-                        crossterm::event::KeyCode::Down => {
-                            if selected_index < options.len() - 1 {
-                                selected_index += 1;
-                            }
-                            render_menu(&mut stdout, &options, selected_index)?;
-                        }
-                        crossterm::event::KeyCode::Enter => {
-                            break;
-                        }
-                        _ => {}
-                    }
-                }
+        let event = crossterm::event::read().context(TerminalSnafu {
+            operator: "read event",
+        })?;
+        let key = match event {
+            Event::Key(key) if key.is_press() => key,
+            Event::Resize(_, _) => {
+                picker.render(&mut stdout)?;
+                continue;
             }
-            crossterm::event::Event::Resize(_, _) => {
-                render_menu(&mut stdout, &options, selected_index)?;
+            _ => continue,
+        };
+        let (_, height) = crossterm::terminal::size().context(TerminalSnafu {
+            operator: "get terminal size",
+        })?;
+        let page = Picker::page_size(height) as isize;
+        match key.code {
+            // raw mode turns Ctrl-C into a key press instead of a signal
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(None);
             }
-            _ => {}
+            // leaves the query of multi-select mode, keeping the filter
+            KeyCode::Enter | KeyCode::Esc if multiple && picker.editing => {
+                picker.editing = false;
+            }
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Enter => match picker.confirm() {
+                chosen if chosen.is_empty() => continue,
+                chosen => return Ok(Some(chosen)),
+            },
+            KeyCode::Up => picker.move_by(-1),
+            KeyCode::Down => picker.move_by(1),
+            KeyCode::PageUp => picker.move_by(-page),
+            KeyCode::PageDown => picker.move_by(page),
+            KeyCode::Home => picker.selected = 0,
+            KeyCode::End => picker.move_by(isize::MAX),
+            KeyCode::Backspace if picker.editing => {
+                picker.query.pop();
+                picker.filter();
+            }
+            KeyCode::Char(c) if picker.editing => {
+                picker.query.push(c);
+                picker.filter();
+            }
+            KeyCode::Char(' ') if multiple => picker.toggle(),
+            KeyCode::Char('a') if multiple => picker.toggle_all(),
+            KeyCode::Char('/') if multiple => picker.editing = true,
+            _ => continue,
         }
+        picker.render(&mut stdout)?;
+    }
+}
+
+/// Lets the user pick one of `options`, filtered by a fuzzy search as they
+/// type. Returns the index of the option, `None` when cancelled with Esc or
+/// Ctrl-C.
+pub fn select(options: &[&str]) -> Result<Option<usize>> {
+    Ok(pick(options, false)?.and_then(|chosen| chosen.first().copied()))
+}
+
+/// Lets the user toggle several of `options` with space, or all matches of
+/// the filter with `a`. Returns the indices of the chosen options in order,
+/// `None` when cancelled.
+pub fn select_many(options: &[&str]) -> Result<Option<Vec<usize>>> {
+    pick(options, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("", "anything"), Some((0, vec![])));
+        assert_eq!(fuzzy_match("xyz", "fix the parser"), None);
+        let (_, positions) = fuzzy_match("PS", "update params, parse sql").unwrap();
+        assert_eq!(positions, vec![15, 18]);
+        let (contiguous, _) = fuzzy_match("fix", "fix bug").unwrap();
+        let (scattered, _) = fuzzy_match("fix", "f i x").unwrap();
+        assert!(contiguous > scattered);
+    }
+
+    #[test]
+    fn test_multi_select() {
+        let options = ["write docs", "fix parser", "fix docs"];
+        let mut picker = Picker::new(&options, true);
+        assert_eq!(picker.confirm(), vec![0]);
+        picker.query = "fix".to_owned();
+        picker.filter();
+        picker.toggle_all();
+        assert_eq!(picker.confirm(), vec![1, 2]);
+        picker.toggle();
+        assert_eq!(picker.confirm(), vec![2]);
+        picker.toggle_all();
+        picker.toggle_all();
+        assert_eq!(picker.confirm(), vec![1]);
+    }
+
+    #[test]
+    fn test_task_completer() {
+        let completer = TaskCompleter {
+            labels: vec!["home".to_owned(), "homework".to_owned(), "work".to_owned()],
+            texts: vec!["Write docs".to_owned(), "fix parser".to_owned()],
+        };
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            completer.candidates("buy hom"),
+            (4, strings(&["home", "homework"]))
+        );
+        // a complete label still completes to the longer ones
+        assert_eq!(completer.candidates("home"), (0, strings(&["homework"])));
+        assert_eq!(completer.candidates("write"), (0, strings(&["Write docs"])));
+        assert_eq!(completer.candidates(""), (0, Vec::new()));
+    }
+
+    #[test]
+    fn test_history_file() {
+        assert_eq!(
+            history_file("/todo", false, false),
+            Some(std::path::PathBuf::from("/todo/input_history.txt"))
+        );
+        assert_eq!(history_file("/todo", true, false), None);
+        assert_eq!(history_file("/todo", false, true), None);
     }
-    crossterm::terminal::disable_raw_mode().context(TerminalSnafu {
-        operator: "disable_raw_mode",
-    })?;
-    execute!(
-        stdout,
-        crossterm::terminal::LeaveAlternateScreen,
-        crossterm::cursor::Show,
-    )
-    .context(TerminalSnafu {
-        operator: "leave alternate screen",
-    })?;
-    Ok(selected_index)
 }
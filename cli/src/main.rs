@@ -1,9 +1,18 @@
+mod completion;
+mod editor;
+mod git;
 mod interaction;
+mod output;
+mod tui;
+
+use std::io::IsTerminal;
 
 use clap::Parser;
 use interaction::InteractionError;
+use output::{Cell, Printer};
+use serde_json::json;
 use snafu::{ResultExt, prelude::Snafu};
-use todo_core::db;
+use todo_core::{backup, db, encryption, format, link, remote, repo, report, scan, sync};
 
 #[derive(Debug, Snafu)]
 pub enum TodoError {
@@ -12,6 +21,30 @@ pub enum TodoError {
         source: db::DBError,
         cases: &'static str,
     },
+    #[snafu(display("backup error"))]
+    Backup { source: backup::BackupError },
+    #[snafu(display("invalid {} content", format))]
+    Format {
+        source: format::FormatError,
+        format: &'static str,
+    },
+    #[snafu(display("invalid --since or --until"))]
+    DateRange { source: format::FormatError },
+    #[snafu(display("failed to access '{}'", path))]
+    File {
+        source: std::io::Error,
+        path: String,
+    },
+    #[snafu(display("scan error"))]
+    Scan { source: scan::ScanError },
+    #[snafu(display("git error"))]
+    Git { source: git::GitError },
+    #[snafu(display("editor error"))]
+    Editor { source: editor::EditorError },
+    #[snafu(display("tui error"))]
+    Tui { source: tui::TuiError },
+    #[snafu(display("sync error"))]
+    Remote { source: remote::RemoteError },
     #[snafu(display("user interaction error"))]
     Interaction {
         source: InteractionError,
@@ -21,80 +54,295 @@ pub enum TodoError {
     Input { input: String, expect: &'static str },
     #[snafu(display("operator cancelled by user"))]
     UserCancelled,
+    #[snafu(display("failed to read keyfile '{}'", path))]
+    Keyfile {
+        source: std::io::Error,
+        path: String,
+    },
+}
+
+impl TodoError {
+    /// Exit code of the process, stable so that scripts can tell the errors
+    /// apart. 1 is left to panics and 2 to the usage errors of clap.
+    ///
+    /// | code | error             |
+    /// |------|-------------------|
+    /// | 3    | cancelled by user |
+    /// | 4    | database          |
+    /// | 5    | file              |
+    /// | 6    | keyfile           |
+    /// | 7    | import or export  |
+    /// | 8    | date range        |
+    /// | 9    | backup            |
+    /// | 10   | scan              |
+    /// | 11   | git               |
+    /// | 12   | remote sync       |
+    /// | 13   | prompt            |
+    /// | 14   | tui               |
+    /// | 15   | editor            |
+    /// | 16   | invalid input     |
+    fn exit_code(&self) -> i32 {
+        match self {
+            TodoError::UserCancelled => 3,
+            TodoError::Database { .. } => 4,
+            TodoError::File { .. } => 5,
+            TodoError::Keyfile { .. } => 6,
+            TodoError::Format { .. } => 7,
+            TodoError::DateRange { .. } => 8,
+            TodoError::Backup { .. } => 9,
+            TodoError::Scan { .. } => 10,
+            TodoError::Git { .. } => 11,
+            TodoError::Remote { .. } => 12,
+            TodoError::Interaction { .. } => 13,
+            TodoError::Tui { .. } => 14,
+            TodoError::Editor { .. } => 15,
+            TodoError::Input { .. } => 16,
+        }
+    }
 }
 
 type TodoResult<T> = std::result::Result<T, TodoError>;
 
-fn add_task() -> TodoResult<()> {
-    let task = interaction::read_input("task");
-    if task.is_empty() {
+/// Reads stdin when it is not a terminal, so that `echo text | todo add`
+/// works, `None` means the user can be prompted instead.
+fn read_piped_input() -> TodoResult<Option<String>> {
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        return Ok(None);
+    }
+    let content = std::io::read_to_string(stdin).context(FileSnafu { path: "stdin" })?;
+    Ok(Some(content))
+}
+
+/// Completion of tags, projects and open task texts for the task prompts. A
+/// database error only leaves the prompt without completion.
+fn task_completer(repo: Option<&str>) -> interaction::TaskCompleter {
+    let conn = match db::create_connection().and_then(|conn| db::ensure_table(&conn).map(|_| conn))
+    {
+        Ok(conn) => conn,
+        Err(_) => return interaction::TaskCompleter::default(),
+    };
+    interaction::TaskCompleter {
+        labels: db::list_labels(&conn).unwrap_or_default(),
+        texts: db::list_tasks_in(&conn, repo)
+            .map(|tasks| tasks.into_iter().map(|task| task.task).collect())
+            .unwrap_or_default(),
+    }
+}
+
+fn add_task(text: Vec<String>, repo: Option<&str>, out: &mut Printer) -> TodoResult<()> {
+    let tasks: Vec<String> = if !text.is_empty() {
+        vec![text.join(" ")]
+    } else {
+        match read_piped_input()? {
+            Some(content) => content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            None => vec![interaction::read_input("task", task_completer(repo))],
+        }
+    };
+    if tasks.is_empty() || tasks.iter().any(|task| task.trim().is_empty()) {
         return Err(TodoError::Input {
-            input: task,
+            input: String::new(),
             expect: "string task",
         });
     }
     let conn = db::create_connection().context(DatabaseSnafu { cases: "add task" })?;
     db::ensure_table(&conn).context(DatabaseSnafu { cases: "add task" })?;
-    db::insert_task(&conn, &task).context(DatabaseSnafu { cases: "add task" })?;
-    list_tasks()?;
+    for task in &tasks {
+        let id =
+            db::insert_task_in(&conn, task, repo).context(DatabaseSnafu { cases: "add task" })?;
+        if out.is_json() {
+            out.print(
+                &json!({"action": "add", "id": id, "task": task}),
+                String::new,
+            );
+        }
+    }
+    // people see the new task among the open ones
+    if !out.is_json() {
+        list_tasks(repo, out)?;
+    }
     Ok(())
 }
 
-fn select_task(conn: &db::Connection) -> TodoResult<Option<db::OpenTask>> {
-    db::ensure_table(&conn).context(DatabaseSnafu {
+/// Open tasks offered by the interactive pickers.
+fn pickable_tasks(conn: &db::Connection, repo: Option<&str>) -> TodoResult<Vec<db::OpenTask>> {
+    let tasks = db::list_tasks_in(conn, repo).context(DatabaseSnafu {
         cases: "select task",
     })?;
-    let tasks = db::list_tasks(&conn).context(DatabaseSnafu {
+    if tasks.is_empty() {
+        return Err(TodoError::Input {
+            input: String::new(),
+            expect: "an open task to select",
+        });
+    }
+    Ok(tasks)
+}
+
+/// Resolves a reference given as an argument or on stdin to an open task of
+/// the same repositories that `list` shows.
+fn resolve_open_task(
+    conn: &db::Connection,
+    reference: &str,
+    repo: Option<&str>,
+) -> TodoResult<db::OpenTask> {
+    let task = db::resolve_task(conn, reference).context(DatabaseSnafu {
         cases: "select task",
     })?;
-    let mut task_names: Vec<&str> = tasks.iter().map(|x| x.task.as_str()).collect();
-    let cancel_index = task_names.len();
-    task_names.push("cancel");
+    if task.status != db::TaskStatus::Open {
+        return Err(TodoError::Input {
+            input: reference.to_owned(),
+            expect: "an open task",
+        });
+    }
+    if repo.is_some() && task.repo.as_deref() != repo {
+        return Err(TodoError::Input {
+            input: reference.to_owned(),
+            expect: "a task of the current repository, or --global",
+        });
+    }
+    Ok(task.into())
+}
+
+fn select_task(
+    conn: &db::Connection,
+    reference: Option<String>,
+    repo: Option<&str>,
+) -> TodoResult<Option<db::OpenTask>> {
+    db::ensure_table(conn).context(DatabaseSnafu {
+        cases: "select task",
+    })?;
+    if let Some(reference) = reference {
+        return resolve_open_task(conn, &reference, repo).map(Some);
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(TodoError::Input {
+            input: String::new(),
+            expect: "task id or uuid argument",
+        });
+    }
+    let tasks = pickable_tasks(conn, repo)?;
+    let task_names: Vec<&str> = tasks.iter().map(|x| x.task.as_str()).collect();
     let index = interaction::select(&task_names).context(InteractionSnafu {
         cases: "select task",
     })?;
-    Ok(if index == cancel_index {
-        None
-    } else {
-        Some(tasks[index].clone())
-    })
+    Ok(index.map(|index| tasks[index].clone()))
 }
 
-fn select_and_delete_task() -> TodoResult<()> {
-    let conn = db::create_connection().context(DatabaseSnafu {
-        cases: "delete task",
+/// Resolves every reference before anything is changed, so that a typo in
+/// `todo done 12 51` does not leave the first task done. Without references
+/// they are read from piped stdin, or the tasks are picked on a terminal.
+fn select_tasks(
+    conn: &db::Connection,
+    mut references: Vec<String>,
+    repo: Option<&str>,
+) -> TodoResult<Vec<db::OpenTask>> {
+    db::ensure_table(conn).context(DatabaseSnafu {
+        cases: "select task",
     })?;
-    match select_task(&conn)? {
-        Some(task) => {
-            db::delete_task(&conn, task.id).context(DatabaseSnafu {
-                cases: "delete task",
+    if references.is_empty() {
+        let Some(content) = read_piped_input()? else {
+            let tasks = pickable_tasks(conn, repo)?;
+            let task_names: Vec<&str> = tasks.iter().map(|x| x.task.as_str()).collect();
+            let chosen = interaction::select_many(&task_names).context(InteractionSnafu {
+                cases: "select task",
             })?;
-            println!("delete task({}): {} ", task.id, task.task);
-            Ok(())
+            return match chosen {
+                Some(chosen) => Ok(chosen
+                    .into_iter()
+                    .map(|index| tasks[index].clone())
+                    .collect()),
+                None => Err(TodoError::UserCancelled {}),
+            };
+        };
+        references = content.split_whitespace().map(str::to_owned).collect();
+        if references.is_empty() {
+            return Err(TodoError::Input {
+                input: content,
+                expect: "task ids or uuids",
+            });
         }
-        None => Err(TodoError::UserCancelled {}),
     }
+    references
+        .iter()
+        .map(|reference| resolve_open_task(conn, reference, repo))
+        .collect()
 }
 
-fn select_and_done_task() -> TodoResult<()> {
-    let conn = db::create_connection().context(DatabaseSnafu {
-        cases: "delete task",
-    })?;
-    match select_task(&conn)? {
-        Some(task) => {
-            db::done_task(&conn, task.id).context(DatabaseSnafu { cases: "done task" })?;
-            println!("done task({}): '{}'", task.id, task.task);
-            Ok(())
-        }
-        None => Err(TodoError::UserCancelled {}),
+fn select_and_delete_task(
+    references: Vec<String>,
+    repo: Option<&str>,
+    out: &mut Printer,
+) -> TodoResult<()> {
+    let cases = "delete task";
+    let conn = db::create_connection().context(DatabaseSnafu { cases })?;
+    let tasks = select_tasks(&conn, references, repo)?;
+    db::in_transaction(&conn, |conn| {
+        tasks
+            .iter()
+            .try_for_each(|task| db::delete_task(conn, task.id))
+    })
+    .context(DatabaseSnafu { cases })?;
+    for task in tasks {
+        out.print(
+            &json!({"action": "delete", "id": task.id, "task": task.task}),
+            || format!("delete task({}): {} ", task.id, task.task),
+        );
     }
+    Ok(())
 }
 
-fn select_and_edit_task() -> TodoResult<()> {
+fn select_and_done_task(
+    references: Vec<String>,
+    repo: Option<&str>,
+    out: &mut Printer,
+) -> TodoResult<()> {
+    let cases = "done task";
+    let conn = db::create_connection().context(DatabaseSnafu { cases })?;
+    let tasks = select_tasks(&conn, references, repo)?;
+    db::in_transaction(&conn, |conn| {
+        tasks
+            .iter()
+            .try_for_each(|task| db::done_task(conn, task.id))
+    })
+    .context(DatabaseSnafu { cases })?;
+    for task in tasks {
+        print_done(out, task.id, &task.task);
+    }
+    Ok(())
+}
+
+fn select_and_edit_task(
+    reference: Option<String>,
+    text: Vec<String>,
+    use_editor: bool,
+    repo: Option<&str>,
+    out: &mut Printer,
+) -> TodoResult<()> {
     let conn = db::create_connection().context(DatabaseSnafu { cases: "edit task" })?;
-    match select_task(&conn)? {
+    match select_task(&conn, reference, repo)? {
         Some(task) => {
-            let new_task = interaction::read_input_with_default("task", &task.task);
+            let new_task = if !text.is_empty() {
+                text.join(" ")
+            } else if use_editor {
+                editor::edit(&task.task)
+                    .context(EditorSnafu {})?
+                    .trim_end()
+                    .to_owned()
+            } else {
+                match read_piped_input()? {
+                    Some(content) => content.trim().to_owned(),
+                    None => interaction::read_input_with_default(
+                        "task",
+                        &task.task,
+                        task_completer(repo),
+                    ),
+                }
+            };
             if new_task.is_empty() {
                 return Err(TodoError::Input {
                     input: new_task,
@@ -104,9 +352,14 @@ fn select_and_edit_task() -> TodoResult<()> {
             let old_task = task.task;
             db::edit_task(&conn, task.id, &new_task)
                 .context(DatabaseSnafu { cases: "edit task" })?;
-            println!(
-                "edit task({}):\n\t'{}'\n\t-> '{}'",
-                task.id, old_task, new_task
+            out.print(
+                &json!({"action": "edit", "id": task.id, "task": new_task, "previous": old_task}),
+                || {
+                    format!(
+                        "edit task({}):\n\t'{}'\n\t-> '{}'",
+                        task.id, old_task, new_task
+                    )
+                },
             );
             Ok(())
         }
@@ -114,41 +367,130 @@ fn select_and_edit_task() -> TodoResult<()> {
     }
 }
 
-fn list_tasks() -> TodoResult<()> {
+fn print_done(out: &mut Printer, id: i64, task: &str) {
+    out.print(&json!({"action": "done", "id": id, "task": task}), || {
+        format!("done task({}): '{}'", id, task)
+    });
+}
+
+fn status_name(status: &db::TaskStatus) -> &'static str {
+    match status {
+        db::TaskStatus::Open => "OPEN",
+        db::TaskStatus::Closed => "CLOSE",
+        db::TaskStatus::Deleted => "DELETE",
+    }
+}
+
+/// Colors of the STATUS column, closed and deleted ones as in `todo tui`.
+fn status_color(status: &db::TaskStatus) -> crossterm::style::Color {
+    match status {
+        db::TaskStatus::Open => crossterm::style::Color::Yellow,
+        db::TaskStatus::Closed => crossterm::style::Color::Green,
+        db::TaskStatus::Deleted => crossterm::style::Color::DarkGrey,
+    }
+}
+
+/// Opens every open task in the editor and applies the changed lines in one
+/// transaction.
+fn edit_all_tasks(repo: Option<&str>, out: &mut Printer) -> TodoResult<()> {
+    let cases = "edit tasks";
+    let conn = db::create_connection().context(DatabaseSnafu { cases })?;
+    db::ensure_table(&conn).context(DatabaseSnafu { cases })?;
+    let tasks = db::list_tasks_in(&conn, repo).context(DatabaseSnafu { cases })?;
+    let content = editor::edit(&editor::render_bulk(&tasks)).context(EditorSnafu {})?;
+    let edit = editor::parse_bulk(&content, &tasks).context(EditorSnafu {})?;
+    if edit == editor::BulkEdit::default() {
+        if !out.is_json() {
+            println!("no changes");
+        }
+        return Ok(());
+    }
+    let added = db::in_transaction(&conn, |conn| {
+        for (id, text) in &edit.edited {
+            db::edit_task(conn, *id, text)?;
+        }
+        for id in &edit.deleted {
+            db::delete_task(conn, *id)?;
+        }
+        edit.added
+            .iter()
+            .map(|text| db::insert_task_in(conn, text, repo))
+            .collect::<db::Result<Vec<i64>>>()
+    })
+    .context(DatabaseSnafu { cases })?;
+    for (id, text) in &edit.edited {
+        out.print(&json!({"action": "edit", "id": id, "task": text}), || {
+            format!("edit task({}): '{}'", id, text)
+        });
+    }
+    for task in tasks.iter().filter(|task| edit.deleted.contains(&task.id)) {
+        out.print(
+            &json!({"action": "delete", "id": task.id, "task": task.task}),
+            || format!("delete task({}): {} ", task.id, task.task),
+        );
+    }
+    for (id, text) in added.iter().zip(&edit.added) {
+        out.print(&json!({"action": "add", "id": id, "task": text}), || {
+            format!("add task({}): '{}'", id, text)
+        });
+    }
+    Ok(())
+}
+
+fn list_tasks(repo: Option<&str>, out: &mut Printer) -> TodoResult<()> {
     let conn = db::create_connection().context(DatabaseSnafu { cases: "list task" })?;
     db::ensure_table(&conn).context(DatabaseSnafu { cases: "list task" })?;
-    let tasks = db::list_tasks(&conn).context(DatabaseSnafu { cases: "list task" })?;
-    for task in tasks {
-        println!("{}({}): {}", task.id, task.create_time, task.task);
-    }
+    let tasks = db::list_tasks_in(&conn, repo).context(DatabaseSnafu { cases: "list task" })?;
+    out.print_rows(
+        &["ID", "CREATED", "TASK"],
+        &tasks,
+        |task| {
+            vec![
+                task.id.to_string().into(),
+                output::age(&task.create_time).into(),
+                task.task.as_str().into(),
+            ]
+        },
+        |task| format!("{}({}): {}", task.id, task.create_time, task.task),
+    );
     Ok(())
 }
 
-fn list_all_tasks() -> TodoResult<()> {
+fn list_all_tasks(repo: Option<&str>, out: &mut Printer) -> TodoResult<()> {
     let conn = db::create_connection().context(DatabaseSnafu { cases: "list task" })?;
     db::ensure_table(&conn).context(DatabaseSnafu { cases: "list task" })?;
-    let tasks = db::list_all_tasks(&conn).context(DatabaseSnafu { cases: "list task" })?;
-    for task in tasks {
-        println!(
-            "{}[{}]({} - {}): {}",
-            task.id,
-            match task.status {
-                db::TaskStatus::Open => "OPEN",
-                db::TaskStatus::Closed => "CLOSE",
-                db::TaskStatus::Deleted => "DELETE",
-            },
-            task.create_time,
-            match task.finished_time {
-                Some(finished_time) => finished_time,
-                None => "".to_owned(),
-            },
-            task.task
-        );
-    }
+    let tasks = db::list_all_tasks_in(&conn, repo).context(DatabaseSnafu { cases: "list task" })?;
+    out.print_rows(
+        &["ID", "STATUS", "CREATED", "FINISHED", "TASK"],
+        &tasks,
+        |task| {
+            vec![
+                task.id.to_string().into(),
+                Cell::colored(status_name(&task.status), status_color(&task.status)),
+                output::age(&task.create_time).into(),
+                task.finished_time
+                    .as_deref()
+                    .map(output::age)
+                    .unwrap_or_default()
+                    .into(),
+                task.task.as_str().into(),
+            ]
+        },
+        |task| {
+            format!(
+                "{}[{}]({} - {}): {}",
+                task.id,
+                status_name(&task.status),
+                task.create_time,
+                task.finished_time.as_deref().unwrap_or_default(),
+                task.task
+            )
+        },
+    );
     Ok(())
 }
 
-fn clean_tasks() -> TodoResult<()> {
+fn clean_tasks(repo: Option<&str>, out: &mut Printer) -> TodoResult<()> {
     let conn = db::create_connection().context(DatabaseSnafu {
         cases: "clean task",
     })?;
@@ -158,36 +500,698 @@ fn clean_tasks() -> TodoResult<()> {
     db::clean_outdate_task(&conn).context(DatabaseSnafu {
         cases: "clean task",
     })?;
-    list_all_tasks()?;
+    list_all_tasks(repo, out)?;
+    Ok(())
+}
+
+fn sync_tasks(path: &str, create: bool, out: &mut Printer) -> TodoResult<()> {
+    let is_url = path.starts_with("http://") || path.starts_with("https://");
+    // a typo would otherwise create a new database and copy every task there
+    if !is_url && !create && !std::path::Path::new(path).exists() {
+        return Err(TodoError::Input {
+            input: path.to_owned(),
+            expect: "an existing database file, or --create",
+        });
+    }
+    let conn = db::create_connection().context(DatabaseSnafu { cases: "sync task" })?;
+    let report = if is_url {
+        remote::sync_with_server(&conn, path).context(RemoteSnafu {})?
+    } else {
+        let remote = db::open_connection(path).context(DatabaseSnafu { cases: "sync task" })?;
+        sync::sync_databases(&conn, &remote).context(DatabaseSnafu { cases: "sync task" })?
+    };
+    for (name, report) in [("local", report.local), ("remote", report.remote)] {
+        let record = json!({
+            "side": name,
+            "inserted": report.inserted,
+            "updated": report.updated,
+            "deleted": report.deleted,
+        });
+        out.print(&record, || {
+            format!(
+                "{}: {} inserted, {} updated, {} deleted",
+                name, report.inserted, report.updated, report.deleted
+            )
+        });
+    }
+    Ok(())
+}
+
+fn read_passphrase(keyfile: Option<String>, cases: &'static str) -> TodoResult<String> {
+    let key = match keyfile {
+        Some(path) => std::fs::read_to_string(&path)
+            .context(KeyfileSnafu { path })?
+            .trim_end_matches(['\r', '\n'])
+            .to_owned(),
+        None => match encryption::configured_key().context(DatabaseSnafu { cases })? {
+            Some(key) => key,
+            None => interaction::read_secret("passphrase"),
+        },
+    };
+    if key.is_empty() {
+        return Err(TodoError::Input {
+            input: key,
+            expect: "non-empty passphrase",
+        });
+    }
+    Ok(key)
+}
+
+fn encrypt_database(keyfile: Option<String>, out: &mut Printer) -> TodoResult<()> {
+    let cases = "encrypt database";
+    let key = read_passphrase(keyfile, cases)?;
+    let db_path = db::get_db_path().context(DatabaseSnafu { cases })?;
+    encryption::encrypt_database(&db_path, &key).context(DatabaseSnafu { cases })?;
+    out.print(&json!({"action": "encrypt", "path": db_path}), || {
+        format!(
+            "encrypted '{}', unlock it with {} or '{}' in the todo folder",
+            db_path,
+            encryption::PASSPHRASE_ENV,
+            encryption::DEFAULT_KEYFILE
+        )
+    });
+    Ok(())
+}
+
+fn decrypt_database(keyfile: Option<String>, out: &mut Printer) -> TodoResult<()> {
+    let cases = "decrypt database";
+    let key = read_passphrase(keyfile, cases)?;
+    let db_path = db::get_db_path().context(DatabaseSnafu { cases })?;
+    encryption::decrypt_database(&db_path, &key).context(DatabaseSnafu { cases })?;
+    out.print(&json!({"action": "decrypt", "path": db_path}), || {
+        format!(
+            "decrypted '{}', remove {} and the keyfile to open it",
+            db_path,
+            encryption::PASSPHRASE_ENV
+        )
+    });
+    Ok(())
+}
+
+fn create_backup(policy: backup::RotationPolicy, out: &mut Printer) -> TodoResult<()> {
+    let conn = db::create_connection().context(DatabaseSnafu { cases: "backup" })?;
+    db::ensure_table(&conn).context(DatabaseSnafu { cases: "backup" })?;
+    let snapshot = backup::create_snapshot(&conn).context(BackupSnafu {})?;
+    let path = snapshot.path.display();
+    out.print(
+        &json!({"action": "create", "path": path.to_string()}),
+        || format!("created snapshot '{}'", path),
+    );
+    for snapshot in backup::rotate_snapshots(policy).context(BackupSnafu {})? {
+        out.print(&json!({"action": "remove", "name": snapshot.name}), || {
+            format!("removed snapshot '{}'", snapshot.name)
+        });
+    }
+    Ok(())
+}
+
+fn list_backups(out: &mut Printer) -> TodoResult<()> {
+    for snapshot in backup::list_snapshots().context(BackupSnafu {})? {
+        let summary = backup::summarize_snapshot(&snapshot).context(BackupSnafu {})?;
+        let time = snapshot.time.format("%Y-%m-%d %H:%M:%S").to_string();
+        let record = json!({
+            "name": snapshot.name,
+            "time": time,
+            "open": summary.open,
+            "total": summary.total,
+        });
+        out.print(&record, || {
+            format!(
+                "{} ({}): {} open, {} total",
+                snapshot.name, time, summary.open, summary.total
+            )
+        });
+    }
     Ok(())
 }
 
-fn todo_main() -> TodoResult<()> {
-    let m = interaction::Cli::parse();
+fn restore_backup(name: &str, out: &mut Printer) -> TodoResult<()> {
+    let snapshot = backup::find_snapshot(name).context(BackupSnafu {})?;
+    let mut conn = db::create_connection().context(DatabaseSnafu { cases: "restore" })?;
+    let current = backup::restore_snapshot(&mut conn, &snapshot).context(BackupSnafu {})?;
+    let record = json!({"action": "restore", "name": snapshot.name, "previous": current.name});
+    out.print(&record, || {
+        format!(
+            "restored '{}', previous state saved as '{}'",
+            snapshot.name, current.name
+        )
+    });
+    Ok(())
+}
+
+fn csv_column(column: interaction::Column) -> format::csv::Column {
+    match column {
+        interaction::Column::Id => format::csv::Column::Id,
+        interaction::Column::Uuid => format::csv::Column::Uuid,
+        interaction::Column::Status => format::csv::Column::Status,
+        interaction::Column::CreateTime => format::csv::Column::CreateTime,
+        interaction::Column::FinishedTime => format::csv::Column::FinishedTime,
+        interaction::Column::DueTime => format::csv::Column::DueTime,
+        interaction::Column::Task => format::csv::Column::Task,
+        interaction::Column::Priority => format::csv::Column::Priority,
+        interaction::Column::Project => format::csv::Column::Project,
+        interaction::Column::Tags => format::csv::Column::Tags,
+    }
+}
+
+fn export_tasks(
+    format: interaction::Format,
+    file: Option<String>,
+    group: interaction::Group,
+    columns: Vec<interaction::Column>,
+    range: format::DateRange,
+) -> TodoResult<()> {
+    if let interaction::Format::Taskwarrior = format {
+        return Err(TodoError::Input {
+            input: "taskwarrior".to_owned(),
+            expect: "json, todotxt, ics, md or csv, taskwarrior is import only",
+        });
+    }
+    let conn = db::create_connection().context(DatabaseSnafu { cases: "export" })?;
+    db::ensure_table(&conn).context(DatabaseSnafu { cases: "export" })?;
+    let tasks = db::list_all_tasks(&conn).context(DatabaseSnafu { cases: "export" })?;
+    let tasks: Vec<db::Task> = tasks
+        .into_iter()
+        .filter(|task| range.contains(task))
+        .collect();
+    let mut content = match format {
+        interaction::Format::Json => {
+            format::json::export(&tasks).context(FormatSnafu { format: "json" })?
+        }
+        interaction::Format::Todotxt => format::todotxt::export(&tasks),
+        interaction::Format::Ics => format::ics::export(&tasks),
+        interaction::Format::Md => {
+            let group = match group {
+                interaction::Group::Status => format::markdown::Group::Status,
+                interaction::Group::Project => format::markdown::Group::Project,
+                interaction::Group::Tag => format::markdown::Group::Tag,
+            };
+            format::markdown::export(&tasks, group)
+        }
+        interaction::Format::Csv => {
+            let columns = match columns.is_empty() {
+                true => format::csv::Column::DEFAULT.to_vec(),
+                false => columns.into_iter().map(csv_column).collect(),
+            };
+            format::csv::export(&tasks, &columns)
+        }
+        interaction::Format::Taskwarrior => unreachable!(),
+    };
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    match file {
+        Some(path) => {
+            std::fs::write(&path, content).context(FileSnafu { path: &path })?;
+            eprintln!("exported {} tasks to '{}'", tasks.len(), path);
+        }
+        None => print!("{}", content),
+    }
+    Ok(())
+}
+
+fn import_tasks(
+    format: interaction::Format,
+    mode: interaction::ImportMode,
+    path: &str,
+    out: &mut Printer,
+) -> TodoResult<()> {
+    if let interaction::Format::Csv = format {
+        return Err(TodoError::Input {
+            input: "csv".to_owned(),
+            expect: "json, todotxt, ics, md or taskwarrior, csv is export only",
+        });
+    }
+    let content = if path == "-" {
+        std::io::read_to_string(std::io::stdin()).context(FileSnafu { path })?
+    } else {
+        std::fs::read_to_string(path).context(FileSnafu { path })?
+    };
+    let tasks = match format {
+        interaction::Format::Json => {
+            format::json::parse(&content).context(FormatSnafu { format: "json" })?
+        }
+        interaction::Format::Todotxt => format::todotxt::parse(&content),
+        interaction::Format::Ics => {
+            format::ics::parse(&content).context(FormatSnafu { format: "ics" })?
+        }
+        interaction::Format::Md => format::markdown::parse(&content),
+        interaction::Format::Csv => unreachable!(),
+        interaction::Format::Taskwarrior => {
+            let (tasks, report) = format::taskwarrior::parse(&content).context(FormatSnafu {
+                format: "taskwarrior",
+            })?;
+            for skipped in report.skipped {
+                eprintln!("skipped '{}': {}", skipped.task, skipped.reason);
+            }
+            for (field, count) in report.dropped {
+                eprintln!("dropped '{}' of {} tasks", field, count);
+            }
+            tasks
+        }
+    };
+    let mode = match mode {
+        interaction::ImportMode::Merge => format::ImportMode::Merge,
+        interaction::ImportMode::Replace => format::ImportMode::Replace,
+    };
+    let conn = db::create_connection().context(DatabaseSnafu { cases: "import" })?;
+    let report =
+        format::import_tasks(&conn, &tasks, mode).context(DatabaseSnafu { cases: "import" })?;
+    let record = json!({
+        "inserted": report.inserted,
+        "updated": report.updated,
+        "duplicates": report.duplicates,
+        "removed": report.removed,
+    });
+    out.print(&record, || {
+        format!(
+            "{} inserted, {} updated, {} duplicates, {} removed",
+            report.inserted, report.updated, report.duplicates, report.removed
+        )
+    });
+    Ok(())
+}
+
+fn scan_tasks(path: &str, out: &mut Printer) -> TodoResult<()> {
+    let conn = db::create_connection().context(DatabaseSnafu { cases: "scan" })?;
+    let report = scan::scan_directory(&conn, path).context(ScanSnafu {})?;
+    for comment in &report.added {
+        let record = json!({"action": "add", "task": comment.text, "source": comment.source()});
+        out.print(&record, || {
+            format!("add task: '{}' ({})", comment.text, comment.source())
+        });
+    }
+    for task in &report.closed {
+        print_done(out, task.id, &task.task);
+    }
+    // the counts are implied by the records of json output
+    if !out.is_json() {
+        println!(
+            "{} added, {} moved, {} closed",
+            report.added.len(),
+            report.moved,
+            report.closed.len()
+        );
+    }
+    Ok(())
+}
+
+fn start_task(reference: Option<String>, repo: Option<&str>, out: &mut Printer) -> TodoResult<()> {
+    let cases = "start task";
+    let conn = db::create_connection().context(DatabaseSnafu { cases })?;
+    let Some(task) = select_task(&conn, reference, repo)? else {
+        return Err(TodoError::UserCancelled {});
+    };
+    let branch = link::branch_name(task.id, &task.task);
+    let created = git::checkout(&branch).context(GitSnafu {})?;
+    link::add_link(&conn, &task.uuid, link::LinkKind::Branch, &branch)
+        .context(DatabaseSnafu { cases })?;
+    let record = json!({
+        "action": "start",
+        "id": task.id,
+        "task": task.task,
+        "branch": branch,
+        "created": created,
+    });
+    out.print(&record, || {
+        format!(
+            "{} branch '{}' of task({}): '{}'",
+            if created { "created" } else { "switched to" },
+            branch,
+            task.id,
+            task.task
+        )
+    });
+    Ok(())
+}
+
+fn link_task(
+    reference: &str,
+    target: Option<String>,
+    kind: Option<interaction::LinkKind>,
+    remove: bool,
+    out: &mut Printer,
+) -> TodoResult<()> {
+    let cases = "link task";
+    let conn = db::create_connection().context(DatabaseSnafu { cases })?;
+    db::ensure_table(&conn).context(DatabaseSnafu { cases })?;
+    let task = db::resolve_task(&conn, reference).context(DatabaseSnafu { cases })?;
+    let Some(target) = target else {
+        let links = link::list_links(&conn, &task.uuid).context(DatabaseSnafu { cases })?;
+        out.print_rows(
+            &["KIND", "CREATED", "TARGET"],
+            &links,
+            |link| {
+                vec![
+                    link.kind.as_str().into(),
+                    output::age(&link.create_time).into(),
+                    link.target.as_str().into(),
+                ]
+            },
+            |link| {
+                format!(
+                    "{}({}): {}",
+                    link.kind.as_str(),
+                    link.create_time,
+                    link.target
+                )
+            },
+        );
+        return Ok(());
+    };
+    if remove {
+        if !link::remove_link(&conn, &task.uuid, &target).context(DatabaseSnafu { cases })? {
+            return Err(TodoError::Input {
+                input: target,
+                expect: "a target linked to the task",
+            });
+        }
+        out.print(
+            &json!({"action": "unlink", "id": task.id, "target": target}),
+            || format!("unlink task({}): '{}'", task.id, target),
+        );
+        return Ok(());
+    }
+    let kind = match kind {
+        Some(interaction::LinkKind::Branch) => link::LinkKind::Branch,
+        Some(interaction::LinkKind::Commit) => link::LinkKind::Commit,
+        Some(interaction::LinkKind::Url) => link::LinkKind::Url,
+        None => link::LinkKind::infer(&target),
+    };
+    link::add_link(&conn, &task.uuid, kind, &target).context(DatabaseSnafu { cases })?;
+    let record = json!({"action": "link", "id": task.id, "kind": kind, "target": target});
+    out.print(&record, || {
+        format!("link task({}) to {} '{}'", task.id, kind.as_str(), target)
+    });
+    Ok(())
+}
+
+fn install_git_hooks(close: bool, force: bool, out: &mut Printer) -> TodoResult<()> {
+    let path = "current executable";
+    let exe = std::env::current_exe().context(FileSnafu { path })?;
+    let hooks = git::install_hooks(&exe.to_string_lossy(), close, force).context(GitSnafu {})?;
+    for hook in hooks {
+        let path = hook.display();
+        out.print(
+            &json!({"action": "install", "path": path.to_string()}),
+            || format!("installed '{}'", path),
+        );
+    }
+    Ok(())
+}
+
+/// Warns about commit messages referencing tasks which do not exist, so that
+/// a typo does not silently lose the link. Ids are local to each database, so
+/// neither unknown tasks nor database errors block the commit.
+fn check_commit_message(path: &str) -> TodoResult<()> {
+    let cases = "check commit message";
+    let content = std::fs::read_to_string(path).context(FileSnafu { path })?;
+    // git strips comment lines from the message
+    let message: Vec<&str> = content
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect();
+    let result = db::create_connection()
+        .and_then(|conn| db::ensure_table(&conn).map(|_| conn))
+        .and_then(|conn| {
+            for reference in link::parse_references(&message.join("\n")) {
+                if db::get_task(&conn, reference.id)?.is_none() {
+                    eprintln!("Warning: todo#{} does not exist", reference.id);
+                }
+            }
+            Ok(())
+        })
+        .context(DatabaseSnafu { cases });
+    if let Err(e) = result {
+        eprintln!("Warning: {}", snafu::Report::from_error(&e));
+    }
+    Ok(())
+}
+
+fn record_commit(close: bool, out: &mut Printer) -> TodoResult<()> {
+    let cases = "record commit";
+    let (sha, message) = git::head_commit().context(GitSnafu {})?;
+    let conn = db::create_connection().context(DatabaseSnafu { cases })?;
+    db::ensure_table(&conn).context(DatabaseSnafu { cases })?;
+    for reference in link::parse_references(&message) {
+        let Some(task) = db::get_task(&conn, reference.id).context(DatabaseSnafu { cases })? else {
+            eprintln!("Warning: todo#{} does not exist", reference.id);
+            continue;
+        };
+        link::add_link(&conn, &task.uuid, link::LinkKind::Commit, &sha)
+            .context(DatabaseSnafu { cases })?;
+        if close && reference.closes && task.status == db::TaskStatus::Open {
+            db::done_task(&conn, task.id).context(DatabaseSnafu { cases })?;
+            print_done(out, task.id, &task.task);
+        }
+    }
+    Ok(())
+}
+
+/// Whether the command changes tasks and is worth a snapshot first. Git
+/// hooks run on every commit, and a snapshot before `encrypt` would be a
+/// plaintext copy of the data.
+fn changes_tasks(command: &interaction::Commands) -> bool {
+    use interaction::Commands;
+    match command {
+        Commands::Add { .. }
+        | Commands::Del { .. }
+        | Commands::Done { .. }
+        | Commands::Edit { .. }
+        | Commands::Clean {}
+        | Commands::Sync { .. }
+        | Commands::Scan { .. }
+        | Commands::Import { .. }
+        | Commands::Start { .. } => true,
+        Commands::Link { target, .. } => target.is_some(),
+        Commands::List { .. }
+        | Commands::Encrypt { .. }
+        | Commands::Decrypt { .. }
+        | Commands::Backup { .. }
+        | Commands::Export { .. }
+        | Commands::Tui {}
+        | Commands::Stats { .. }
+        | Commands::GitHook { .. }
+        | Commands::Completions { .. }
+        | Commands::Complete { .. } => false,
+    }
+}
+
+/// Takes the periodic snapshot, failures are reported but do not stop the
+/// command the user asked for.
+fn auto_backup() {
+    let result = db::create_connection()
+        .and_then(|conn| db::ensure_table(&conn).map(|_| conn))
+        .context(DatabaseSnafu { cases: "backup" })
+        .and_then(|conn| {
+            backup::auto_backup(&conn, backup::RotationPolicy::default()).context(BackupSnafu {})
+        });
+    if let Err(e) = result {
+        eprintln!("Warning: {}", snafu::Report::from_error(&e));
+    }
+}
+
+fn show_stats(days: usize, weeks: usize, repo: Option<&str>, out: &mut Printer) -> TodoResult<()> {
+    let cases = "report tasks";
+    let conn = db::create_connection().context(DatabaseSnafu { cases })?;
+    db::ensure_table(&conn).context(DatabaseSnafu { cases })?;
+    let options = report::ReportOptions {
+        days,
+        weeks,
+        ..Default::default()
+    };
+    let report = report::build_report(&conn, repo, &options).context(DatabaseSnafu { cases })?;
+    out.print(&report, || {
+        let mut lines = vec![
+            format!("open tasks: {}", report.open),
+            format!(
+                "average time to close: {}",
+                report
+                    .average_close_seconds
+                    .map(output::duration)
+                    .unwrap_or_else(|| "-".to_owned())
+            ),
+            format!(
+                "days in a row with a completed task: {}, longest {}",
+                report.current_streak, report.longest_streak
+            ),
+            String::new(),
+            format!("last {} days", report.days.len()),
+        ];
+        let series = |value: fn(&report::Period) -> usize| -> Vec<usize> {
+            report.days.iter().map(value).collect()
+        };
+        for (name, values) in [
+            ("created", series(|period| period.created)),
+            ("completed", series(|period| period.completed)),
+            ("open", series(|period| period.open)),
+        ] {
+            let range = match (values.iter().min(), values.iter().max()) {
+                (Some(min), Some(max)) => format!("{}-{}", min, max),
+                _ => String::new(),
+            };
+            lines.push(format!(
+                "{:9}  {}  {}",
+                name,
+                output::sparkline(&values),
+                range
+            ));
+        }
+        lines.push(String::new());
+        lines.push(format!("{:10}  CREATED  COMPLETED  OPEN", "WEEK"));
+        for week in &report.weeks {
+            lines.push(format!(
+                "{:10}  {:<7}  {:<9}  {}",
+                week.start, week.created, week.completed, week.open
+            ));
+        }
+        if !report.oldest_open.is_empty() {
+            lines.push(String::new());
+            lines.push("oldest open tasks".to_owned());
+            for task in &report.oldest_open {
+                lines.push(format!(
+                    "{}({}): {}",
+                    task.id,
+                    output::age(&task.create_time),
+                    task.task
+                ));
+            }
+        }
+        lines.join("\n")
+    });
+    Ok(())
+}
+
+/// Prints the open tasks whose id starts with the word being completed, as
+/// `id<TAB>text` lines.
+fn complete_tasks(words: &[String]) -> TodoResult<()> {
+    let Some(prefix) = completion::task_prefix(words) else {
+        return Ok(());
+    };
+    let repo = match words.iter().any(|word| word == "--global") {
+        true => None,
+        false => repo::current_repository().map(|repo| repo.key()),
+    };
+    let cases = "complete tasks";
+    let conn = db::create_connection().context(DatabaseSnafu { cases })?;
+    db::ensure_table(&conn).context(DatabaseSnafu { cases })?;
+    let tasks = db::list_tasks_in(&conn, repo.as_deref()).context(DatabaseSnafu { cases })?;
+    for task in tasks {
+        let id = task.id.to_string();
+        if id.starts_with(prefix) {
+            println!("{}\t{}", id, task.task.lines().next().unwrap_or_default());
+        }
+    }
+    Ok(())
+}
+
+/// The name the binary was run as, so the completions follow a renamed binary.
+fn bin_name() -> String {
+    std::env::args_os()
+        .next()
+        .as_ref()
+        .and_then(|arg| std::path::Path::new(arg).file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "todo".to_owned())
+}
+
+fn todo_main(m: interaction::Cli, out: &mut Printer) -> TodoResult<()> {
+    if changes_tasks(&m.command) {
+        auto_backup();
+    }
+    let repo = match m.global {
+        true => None,
+        false => repo::current_repository().map(|repo| repo.key()),
+    };
+    let repo = repo.as_deref();
     match m.command {
-        interaction::Commands::Add {} => add_task(),
-        interaction::Commands::Del {} => select_and_delete_task(),
-        interaction::Commands::Done {} => select_and_done_task(),
-        interaction::Commands::Edit {} => select_and_edit_task(),
+        interaction::Commands::Add { text } => add_task(text, repo, out),
+        interaction::Commands::Del { tasks } => select_and_delete_task(tasks, repo, out),
+        interaction::Commands::Done { tasks } => select_and_done_task(tasks, repo, out),
+        interaction::Commands::Edit {
+            task,
+            text,
+            editor,
+            all,
+        } => match all {
+            true => edit_all_tasks(repo, out),
+            false => select_and_edit_task(task, text, editor, repo, out),
+        },
 
-        interaction::Commands::Clean {} => clean_tasks(),
+        interaction::Commands::Clean {} => clean_tasks(repo, out),
+        interaction::Commands::Sync { path, create } => sync_tasks(&path, create, out),
+        interaction::Commands::Encrypt { keyfile } => encrypt_database(keyfile, out),
+        interaction::Commands::Decrypt { keyfile } => decrypt_database(keyfile, out),
+        interaction::Commands::Backup {
+            command,
+            daily,
+            weekly,
+        } => match command {
+            None => create_backup(backup::RotationPolicy { daily, weekly }, out),
+            Some(interaction::BackupCommands::List {}) => list_backups(out),
+            Some(interaction::BackupCommands::Restore { snapshot }) => {
+                restore_backup(&snapshot, out)
+            }
+        },
+        interaction::Commands::Export {
+            format,
+            file,
+            group,
+            columns,
+            since,
+            until,
+        } => format::DateRange::new(since.as_deref(), until.as_deref())
+            .context(DateRangeSnafu {})
+            .and_then(|range| export_tasks(format, file, group, columns, range)),
+        interaction::Commands::Scan { path } => scan_tasks(&path, out),
+        interaction::Commands::Import { format, mode, path } => {
+            import_tasks(format, mode, &path, out)
+        }
+        interaction::Commands::Start { task } => start_task(task, repo, out),
+        interaction::Commands::Link {
+            task,
+            target,
+            kind,
+            remove,
+        } => link_task(&task, target, kind, remove, out),
+        interaction::Commands::Tui {} => tui::run(repo.map(str::to_owned)).context(TuiSnafu {}),
+        interaction::Commands::GitHook { command } => match command {
+            interaction::GitHookCommands::Install { close, force } => {
+                install_git_hooks(close, force, out)
+            }
+            interaction::GitHookCommands::CommitMsg { file } => check_commit_message(&file),
+            interaction::GitHookCommands::PostCommit { close } => record_commit(close, out),
+        },
+        interaction::Commands::Stats { days, weeks } => show_stats(days, weeks, repo, out),
+        interaction::Commands::Completions { shell } => {
+            print!("{}", completion::script(shell, &bin_name()));
+            Ok(())
+        }
+        interaction::Commands::Complete { words } => complete_tasks(&words),
 
         interaction::Commands::List { all } => {
             if all {
-                list_all_tasks()
+                list_all_tasks(repo, out)
             } else {
-                list_tasks()
+                list_tasks(repo, out)
             }
         }
     }
 }
 
 fn main() {
-    match todo_main() {
-        Ok(_) => (),
-        Err(e) => {
-            eprintln!("Error stack:\n{}", snafu::Report::from_error(&e));
-            std::process::exit(1);
+    let cli = interaction::Cli::parse();
+    let mut out = Printer::new(cli.output);
+    let result = todo_main(cli, &mut out);
+    let is_json = out.is_json();
+    out.finish();
+    if let Err(e) = result {
+        let report = snafu::Report::from_error(&e).to_string();
+        if is_json {
+            eprintln!("{}", json!({"error": report, "code": e.exit_code()}));
+        } else {
+            eprintln!("Error stack:\n{}", report);
         }
+        std::process::exit(e.exit_code());
     }
 }
@@ -0,0 +1,302 @@
+use std::io::IsTerminal;
+
+use crossterm::style::{Color, Stylize};
+use serde::Serialize;
+
+use crate::interaction::Output;
+
+/// A table cell, colored when the table is printed to a terminal.
+pub struct Cell {
+    text: String,
+    color: Option<Color>,
+}
+
+impl Cell {
+    pub fn colored(text: impl Into<String>, color: Color) -> Self {
+        Self {
+            text: text.into(),
+            color: Some(color),
+        }
+    }
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Self {
+        Self { text, color: None }
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(text: &str) -> Self {
+        text.to_owned().into()
+    }
+}
+
+/// Colors are only written to a terminal, and never with `NO_COLOR` set.
+fn use_color() -> bool {
+    std::io::stdout().is_terminal()
+        && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// Prints the results of a command in the format chosen with `--output`.
+pub struct Printer {
+    output: Output,
+    /// records of `--output json`, printed as one array when the command ends
+    records: Vec<serde_json::Value>,
+}
+
+impl Printer {
+    pub fn new(output: Output) -> Self {
+        Self {
+            output,
+            records: Vec::new(),
+        }
+    }
+
+    pub fn is_json(&self) -> bool {
+        matches!(self.output, Output::Json | Output::Jsonl)
+    }
+
+    /// Prints one result, `line` is only built for plain and table output.
+    pub fn print<T: Serialize>(&mut self, record: &T, line: impl FnOnce() -> String) {
+        match self.output {
+            Output::Plain | Output::Table => println!("{}", line()),
+            Output::Json => self.records.push(to_value(record)),
+            Output::Jsonl => println!("{}", to_value(record)),
+        }
+    }
+
+    /// Prints a list of results. Plain output on a terminal and table output
+    /// align `row` in columns below `header`, otherwise each record is one
+    /// `line`.
+    pub fn print_rows<T: Serialize>(
+        &mut self,
+        header: &[&str],
+        records: &[T],
+        row: impl Fn(&T) -> Vec<Cell>,
+        line: impl Fn(&T) -> String,
+    ) {
+        let color = use_color();
+        match self.output {
+            Output::Table => {}
+            Output::Plain if color => {}
+            _ => {
+                for record in records {
+                    self.print(record, || line(record));
+                }
+                return;
+            }
+        }
+        let width = match std::io::stdout().is_terminal() {
+            true => crossterm::terminal::size()
+                .ok()
+                .map(|(width, _)| width as usize),
+            false => None,
+        };
+        let header = header.iter().map(|cell| Cell::from(*cell)).collect();
+        let rows: Vec<Vec<Cell>> = std::iter::once(header)
+            .chain(records.iter().map(row))
+            .collect();
+        for line in render_table(&rows, width, color) {
+            println!("{}", line);
+        }
+    }
+
+    /// Prints the collected records of `--output json`.
+    pub fn finish(self) {
+        if let Output::Json = self.output {
+            println!("{}", to_value(&self.records));
+        }
+    }
+}
+
+/// Aligns `rows` in columns, the first row being the header. The last column
+/// is wrapped to fit in `width`.
+fn render_table(rows: &[Vec<Cell>], width: Option<usize>, color: bool) -> Vec<String> {
+    let mut widths: Vec<usize> = Vec::new();
+    for row in rows {
+        // the last column is not padded, so it does not count
+        for (index, cell) in row.iter().enumerate().take(row.len().saturating_sub(1)) {
+            let cell_width = cell.text.chars().count();
+            match widths.get_mut(index) {
+                Some(max) => *max = (*max).max(cell_width),
+                None => widths.push(cell_width),
+            }
+        }
+    }
+    let indent: usize = widths.iter().map(|width| width + 2).sum();
+    // below 20 characters wrapped text is harder to read than overflowing
+    let last_width = width
+        .map(|width| width.saturating_sub(indent).max(20))
+        .unwrap_or(usize::MAX);
+    let mut lines = Vec::new();
+    for (row_index, row) in rows.iter().enumerate() {
+        let style = |text: String, cell_color: Option<Color>| match (color, cell_color) {
+            (false, _) => text,
+            (true, _) if row_index == 0 => text.bold().to_string(),
+            (true, Some(cell_color)) => text.with(cell_color).to_string(),
+            (true, None) => text,
+        };
+        let mut line = String::new();
+        let last = row.len().saturating_sub(1);
+        for (index, cell) in row.iter().enumerate() {
+            if index == last {
+                let mut wrapped = wrap(&cell.text, last_width).into_iter();
+                line.push_str(&style(wrapped.next().unwrap_or_default(), cell.color));
+                for continued in wrapped {
+                    lines.push(std::mem::take(&mut line));
+                    line = format!("{}{}", " ".repeat(indent), style(continued, cell.color));
+                }
+            } else {
+                let text = format!("{:width$}  ", cell.text, width = widths[index]);
+                line.push_str(&style(text, cell.color));
+            }
+        }
+        lines.push(line.trim_end().to_owned());
+    }
+    lines
+}
+
+/// How long ago a `YYYY-MM-DD HH:MM:SS` local time was, e.g. "3d ago".
+/// Unparsable times are returned as they are.
+pub fn age(time: &str) -> String {
+    age_at(time, chrono::Local::now().naive_local())
+}
+
+fn age_at(time: &str, now: chrono::NaiveDateTime) -> String {
+    let Ok(then) = chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S") else {
+        return time.to_owned();
+    };
+    let elapsed = now - then;
+    let days = elapsed.num_days();
+    match () {
+        _ if elapsed.num_minutes() < 1 => "just now".to_owned(),
+        _ if elapsed.num_hours() < 1 => format!("{}m ago", elapsed.num_minutes()),
+        _ if days < 1 => format!("{}h ago", elapsed.num_hours()),
+        _ if days < 14 => format!("{}d ago", days),
+        _ if days < 60 => format!("{}w ago", days / 7),
+        _ if days < 365 => format!("{}mo ago", days / 30),
+        _ => format!("{}y ago", days / 365),
+    }
+}
+
+/// Draws `values` as a bar per value, scaled to the largest one.
+pub fn sparkline(values: &[usize]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().copied().max().unwrap_or(0).max(1);
+    values
+        .iter()
+        .map(|value| BARS[value * (BARS.len() - 1) / max])
+        .collect()
+}
+
+/// A duration in its two largest units, e.g. "2d 3h".
+pub fn duration(seconds: i64) -> String {
+    let minutes = seconds.max(0) / 60;
+    let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+    match (days, hours) {
+        (0, 0) => format!("{}m", minutes),
+        (0, _) => format!("{}h {}m", hours, minutes),
+        _ => format!("{}d {}h", days, hours),
+    }
+}
+
+/// Wraps `text` at spaces into lines of at most `width` characters, words
+/// longer than a line are split. Line breaks in `text` are kept.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        let mut line_len = 0;
+        for word in paragraph.split(' ') {
+            let mut word: Vec<char> = word.chars().collect();
+            while word.len() > width {
+                if line_len > 0 {
+                    lines.push(std::mem::take(&mut line));
+                    line_len = 0;
+                }
+                lines.push(word.drain(..width).collect());
+            }
+            if line_len > 0 && line_len + 1 + word.len() > width {
+                lines.push(std::mem::take(&mut line));
+                line_len = 0;
+            }
+            if line_len > 0 {
+                line.push(' ');
+                line_len += 1;
+            }
+            line.extend(&word);
+            line_len += word.len();
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+fn to_value<T: Serialize>(record: &T) -> serde_json::Value {
+    // records are plain structs and maps, which always serialize
+    serde_json::to_value(record).expect("serializable record")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(
+            wrap("fix the parser\nthen ship", 8),
+            vec!["fix the", "parser", "then", "ship"]
+        );
+        assert_eq!(wrap("abcdefghij x", 4), vec!["abcd", "efgh", "ij x"]);
+        assert_eq!(wrap("", 4), vec![""]);
+    }
+
+    #[test]
+    fn test_render_table() {
+        let rows = vec![
+            vec![Cell::from("ID"), Cell::from("TASK")],
+            vec![Cell::from("1"), Cell::from("fix the parser\nthen ship")],
+            vec![Cell::from("12"), Cell::colored("done", Color::Green)],
+        ];
+        assert_eq!(
+            render_table(&rows, None, false),
+            vec![
+                "ID  TASK",
+                "1   fix the parser",
+                "    then ship",
+                "12  done"
+            ]
+        );
+        // the task column keeps at least 20 characters
+        let lines = render_table(&rows[..2], Some(10), false);
+        assert_eq!(
+            lines,
+            vec!["ID  TASK", "1   fix the parser", "    then ship"]
+        );
+        let lines = render_table(&rows[..1], None, true);
+        assert_eq!(lines, vec![format!("{}{}", "ID  ".bold(), "TASK".bold())]);
+    }
+
+    #[test]
+    fn test_age() {
+        let now = chrono::NaiveDateTime::parse_from_str("2025-03-10 12:00:00", "%Y-%m-%d %H:%M:%S")
+            .unwrap();
+        assert_eq!(age_at("2025-03-10 11:59:30", now), "just now");
+        assert_eq!(age_at("2025-03-10 11:15:00", now), "45m ago");
+        assert_eq!(age_at("2025-03-07 10:00:00", now), "3d ago");
+        assert_eq!(age_at("2025-01-20 12:00:00", now), "7w ago");
+        assert_eq!(age_at("2024-03-01 12:00:00", now), "1y ago");
+        assert_eq!(age_at("yesterday", now), "yesterday");
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[0, 1, 7, 14]), "▁▁▄█");
+        assert_eq!(sparkline(&[0, 0]), "▁▁");
+        assert_eq!(duration(59), "0m");
+        assert_eq!(duration(3 * 3600 + 120), "3h 2m");
+        assert_eq!(duration(2 * 86400 + 3 * 3600 + 59), "2d 3h");
+    }
+}
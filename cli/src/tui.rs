@@ -0,0 +1,507 @@
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, SetAttribute, SetForegroundColor};
+use crossterm::{cursor, queue, terminal};
+use snafu::{ResultExt, prelude::Snafu};
+use todo_core::{db, link, watch};
+
+use crate::interaction::{InteractionError, RawScreen};
+use crate::output::wrap;
+
+#[derive(Debug, Snafu)]
+pub enum TuiError {
+    #[snafu(display("terminal error when '{}'", operator))]
+    Terminal {
+        source: std::io::Error,
+        operator: &'static str,
+    },
+    #[snafu(display("failed to prepare the terminal"))]
+    Screen { source: InteractionError },
+    #[snafu(display("database error when {}", cases))]
+    Database {
+        source: db::DBError,
+        cases: &'static str,
+    },
+}
+
+type Result<T> = std::result::Result<T, TuiError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Open,
+    Closed,
+    Deleted,
+    All,
+}
+
+const TABS: [Tab; 4] = [Tab::Open, Tab::Closed, Tab::Deleted, Tab::All];
+
+impl Tab {
+    fn title(self) -> &'static str {
+        match self {
+            Tab::Open => "Open",
+            Tab::Closed => "Closed",
+            Tab::Deleted => "Deleted",
+            Tab::All => "All",
+        }
+    }
+
+    fn contains(self, task: &db::Task) -> bool {
+        match self {
+            Tab::Open => task.status == db::TaskStatus::Open,
+            Tab::Closed => task.status == db::TaskStatus::Closed,
+            Tab::Deleted => task.status == db::TaskStatus::Deleted,
+            Tab::All => true,
+        }
+    }
+}
+
+enum Mode {
+    Normal,
+    /// editing the text of a new task, or of the task with the id
+    Input {
+        id: Option<i64>,
+        text: Vec<char>,
+        cursor: usize,
+    },
+    /// waiting for `y` to delete the task with the id
+    ConfirmDelete {
+        id: i64,
+    },
+}
+
+const HELP: &str = "a add  e edit  d done  D delete  j/k move  tab/h/l filter  r refresh  q quit";
+/// Narrower terminals only show the task list.
+const MIN_DETAIL_WIDTH: u16 = 60;
+
+struct App {
+    conn: db::Connection,
+    repo: Option<String>,
+    /// every task of the repository, the tab filters them
+    tasks: Vec<db::Task>,
+    tab: usize,
+    /// index into the tasks of the tab
+    selected: usize,
+    /// first task shown in the list
+    offset: usize,
+    /// rows of the list, updated by `render`
+    page: usize,
+    mode: Mode,
+    /// result of the last action, shown instead of the help
+    message: String,
+}
+
+impl App {
+    fn new(conn: db::Connection, repo: Option<String>) -> Result<Self> {
+        let mut app = App {
+            conn,
+            repo,
+            tasks: Vec::new(),
+            tab: 0,
+            selected: 0,
+            offset: 0,
+            page: 1,
+            mode: Mode::Normal,
+            message: String::new(),
+        };
+        app.tasks = app.load().context(DatabaseSnafu {
+            cases: "load tasks",
+        })?;
+        Ok(app)
+    }
+
+    fn load(&self) -> db::Result<Vec<db::Task>> {
+        db::list_all_tasks_in(&self.conn, self.repo.as_deref())
+    }
+
+    fn visible(&self) -> Vec<&db::Task> {
+        let tab = TABS[self.tab];
+        self.tasks
+            .iter()
+            .filter(|task| tab.contains(task))
+            .collect()
+    }
+
+    fn current(&self) -> Option<&db::Task> {
+        self.visible().get(self.selected).copied()
+    }
+
+    /// Reloads the tasks, keeping the selected task selected when it is still
+    /// in the tab, or `id` when given.
+    fn reload(&mut self, id: Option<i64>) {
+        let id = id.or_else(|| self.current().map(|task| task.id));
+        match self.load() {
+            Ok(tasks) => self.tasks = tasks,
+            Err(e) => self.message = format!("error: {}", snafu::Report::from_error(&e)),
+        }
+        let position = id.and_then(|id| self.visible().iter().position(|task| task.id == id));
+        let last = self.visible().len().saturating_sub(1);
+        self.selected = position.unwrap_or(self.selected).min(last);
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let last = self.visible().len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    fn switch_tab(&mut self, tab: usize) {
+        let id = self.current().map(|task| task.id);
+        self.tab = tab % TABS.len();
+        self.selected = 0;
+        self.offset = 0;
+        self.reload(id);
+    }
+
+    /// Shows the outcome of a database change and reloads the tasks.
+    fn apply(&mut self, result: db::Result<String>, id: Option<i64>) {
+        match result {
+            Ok(message) => self.message = message,
+            Err(e) => self.message = format!("error: {}", snafu::Report::from_error(&e)),
+        }
+        self.reload(id);
+    }
+
+    fn submit(&mut self, id: Option<i64>, text: String) {
+        let text = text.trim().to_owned();
+        if text.is_empty() {
+            self.message = "task text is empty".to_owned();
+            return;
+        }
+        match id {
+            Some(id) => {
+                let result = db::edit_task(&self.conn, id, &text)
+                    .map(|_| format!("edit task({}): '{}'", id, text));
+                self.apply(result, Some(id));
+            }
+            None => match db::insert_task_in(&self.conn, &text, self.repo.as_deref()) {
+                Ok(id) => {
+                    // show the new task even when another tab was open
+                    if !matches!(TABS[self.tab], Tab::Open | Tab::All) {
+                        self.tab = 0;
+                    }
+                    self.apply(Ok(format!("add task({}): '{}'", id, text)), Some(id));
+                }
+                Err(e) => self.apply(Err(e), None),
+            },
+        }
+    }
+
+    /// Handles a key, returns whether the TUI should quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return true;
+        }
+        match std::mem::replace(&mut self.mode, Mode::Normal) {
+            Mode::Normal => return self.handle_normal_key(key),
+            Mode::Input {
+                id,
+                mut text,
+                mut cursor,
+            } => match key.code {
+                KeyCode::Esc => self.message = "cancelled".to_owned(),
+                KeyCode::Enter => self.submit(id, text.into_iter().collect()),
+                code => {
+                    match code {
+                        KeyCode::Left => cursor = cursor.saturating_sub(1),
+                        KeyCode::Right => cursor = (cursor + 1).min(text.len()),
+                        KeyCode::Home => cursor = 0,
+                        KeyCode::End => cursor = text.len(),
+                        KeyCode::Backspace if cursor > 0 => {
+                            cursor -= 1;
+                            text.remove(cursor);
+                        }
+                        KeyCode::Delete if cursor < text.len() => {
+                            text.remove(cursor);
+                        }
+                        KeyCode::Char(c) => {
+                            text.insert(cursor, c);
+                            cursor += 1;
+                        }
+                        _ => {}
+                    }
+                    self.mode = Mode::Input { id, text, cursor };
+                }
+            },
+            Mode::ConfirmDelete { id } => match key.code {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    let result =
+                        db::delete_task(&self.conn, id).map(|_| format!("delete task({})", id));
+                    self.apply(result, None);
+                }
+                _ => self.message = "cancelled".to_owned(),
+            },
+        }
+        false
+    }
+
+    fn handle_normal_key(&mut self, key: KeyEvent) -> bool {
+        self.message.clear();
+        let page = self.page as isize;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return true,
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::PageDown => self.move_by(page),
+            KeyCode::PageUp => self.move_by(-page),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.move_by(isize::MAX),
+            KeyCode::Tab | KeyCode::Right | KeyCode::Char('l') => self.switch_tab(self.tab + 1),
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Char('h') => {
+                self.switch_tab(self.tab + TABS.len() - 1)
+            }
+            KeyCode::Char(c @ '1'..='4') => self.switch_tab(c as usize - '1' as usize),
+            KeyCode::Char('r') => self.reload(None),
+            KeyCode::Char('a') => {
+                self.mode = Mode::Input {
+                    id: None,
+                    text: Vec::new(),
+                    cursor: 0,
+                }
+            }
+            KeyCode::Char('e') | KeyCode::Enter => {
+                if let Some(task) = self.current() {
+                    let text: Vec<char> = task.task.chars().collect();
+                    self.mode = Mode::Input {
+                        id: Some(task.id),
+                        cursor: text.len(),
+                        text,
+                    };
+                }
+            }
+            KeyCode::Char('d') | KeyCode::Char('x') => {
+                if let Some(task) = self.current()
+                    && task.status == db::TaskStatus::Open
+                {
+                    let (id, text) = (task.id, task.task.clone());
+                    let result = db::done_task(&self.conn, id)
+                        .map(|_| format!("done task({}): '{}'", id, text));
+                    self.apply(result, None);
+                }
+            }
+            KeyCode::Char('D') | KeyCode::Delete => {
+                if let Some(task) = self.current()
+                    && task.status != db::TaskStatus::Deleted
+                {
+                    self.mode = Mode::ConfirmDelete { id: task.id };
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn detail_lines(&self, task: &db::Task, width: usize) -> Vec<String> {
+        let status = match task.status {
+            db::TaskStatus::Open => "open",
+            db::TaskStatus::Closed => "closed",
+            db::TaskStatus::Deleted => "deleted",
+        };
+        let mut fields = vec![
+            ("id", task.id.to_string()),
+            ("uuid", task.uuid.clone()),
+            ("status", status.to_owned()),
+            ("created", task.create_time.clone()),
+        ];
+        let optional = [
+            ("finished", &task.finished_time),
+            ("due", &task.due_time),
+            ("priority", &task.priority),
+            ("project", &task.project),
+            ("source", &task.source),
+            ("repo", &task.repo),
+        ];
+        for (name, value) in optional {
+            if let Some(value) = value {
+                fields.push((name, value.clone()));
+            }
+        }
+        if !task.tags.is_empty() {
+            fields.push(("tags", task.tags.join(" ")));
+        }
+        // links are optional details, a failed query only hides them
+        for link in link::list_links(&self.conn, &task.uuid).unwrap_or_default() {
+            fields.push((link.kind.as_str(), link.target));
+        }
+        let mut lines: Vec<String> = fields
+            .into_iter()
+            .map(|(name, value)| format!("{:<9}{}", name, value))
+            .collect();
+        lines.push(String::new());
+        lines.extend(wrap(&task.task, width));
+        lines
+    }
+
+    fn render(&mut self, stdout: &mut std::io::Stdout) -> Result<()> {
+        let (width, height) = terminal::size().context(TerminalSnafu {
+            operator: "get terminal size",
+        })?;
+        let operator = "render";
+        queue!(
+            stdout,
+            cursor::Hide,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::MoveTo(0, 0)
+        )
+        .context(TerminalSnafu { operator })?;
+        for (index, tab) in TABS.iter().enumerate() {
+            let count = self.tasks.iter().filter(|task| tab.contains(task)).count();
+            let label = format!(" {} {} ", tab.title(), count);
+            let attribute = match index == self.tab {
+                true => Attribute::Reverse,
+                false => Attribute::Reset,
+            };
+            queue!(
+                stdout,
+                SetAttribute(attribute),
+                Print(label),
+                SetAttribute(Attribute::Reset),
+                Print(" ")
+            )
+            .context(TerminalSnafu { operator })?;
+        }
+
+        self.page = (height as usize).saturating_sub(2).max(1);
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + self.page {
+            self.offset = self.selected + 1 - self.page;
+        }
+        let list_width = match width >= MIN_DETAIL_WIDTH {
+            true => width as usize * 3 / 5,
+            false => width as usize,
+        };
+        let visible = self.visible();
+        let details = match (width >= MIN_DETAIL_WIDTH, visible.get(self.selected)) {
+            (true, Some(task)) => self.detail_lines(task, width as usize - list_width - 2),
+            _ => Vec::new(),
+        };
+        for row in 0..self.page {
+            let y = row as u16 + 1;
+            if let Some(task) = visible.get(self.offset + row) {
+                let (mark, color) = match task.status {
+                    db::TaskStatus::Open => ("[ ]", Color::Reset),
+                    db::TaskStatus::Closed => ("[x]", Color::Green),
+                    db::TaskStatus::Deleted => ("[-]", Color::DarkGrey),
+                };
+                let first_line = task.task.lines().next().unwrap_or_default();
+                let line = format!("{:>4} {} {}", task.id, mark, first_line);
+                let line: String = line.chars().take(list_width.saturating_sub(1)).collect();
+                let attribute = match self.offset + row == self.selected {
+                    true => Attribute::Reverse,
+                    false => Attribute::Reset,
+                };
+                queue!(
+                    stdout,
+                    cursor::MoveTo(0, y),
+                    SetForegroundColor(color),
+                    SetAttribute(attribute),
+                    Print(line),
+                    SetAttribute(Attribute::Reset),
+                    SetForegroundColor(Color::Reset),
+                )
+                .context(TerminalSnafu { operator })?;
+            }
+            if width >= MIN_DETAIL_WIDTH {
+                let detail = details.get(row).map(String::as_str).unwrap_or_default();
+                queue!(
+                    stdout,
+                    cursor::MoveTo(list_width as u16, y),
+                    Print(format!("│ {}", detail)),
+                )
+                .context(TerminalSnafu { operator })?;
+            }
+        }
+
+        let bottom = height.saturating_sub(1);
+        queue!(stdout, cursor::MoveTo(0, bottom)).context(TerminalSnafu { operator })?;
+        match &self.mode {
+            Mode::Normal => {
+                let line = match self.message.is_empty() {
+                    true => HELP,
+                    false => &self.message,
+                };
+                let line: String = line.chars().take(width as usize).collect();
+                queue!(
+                    stdout,
+                    SetAttribute(Attribute::Dim),
+                    Print(line),
+                    SetAttribute(Attribute::Reset)
+                )
+                .context(TerminalSnafu { operator })?;
+            }
+            Mode::Input { id, text, cursor } => {
+                let prompt = match id {
+                    Some(id) => format!("edit task({})> ", id),
+                    None => "add> ".to_owned(),
+                };
+                let room = (width as usize).saturating_sub(prompt.len() + 1).max(1);
+                // scroll long text so that the cursor stays visible
+                let start = cursor.saturating_sub(room - 1);
+                let shown: String = text
+                    .iter()
+                    .skip(start)
+                    .take(room)
+                    .map(|c| if *c == '\n' { '↵' } else { *c })
+                    .collect();
+                queue!(
+                    stdout,
+                    Print(&prompt),
+                    Print(shown),
+                    cursor::MoveTo((prompt.len() + cursor - start) as u16, bottom),
+                    cursor::Show,
+                )
+                .context(TerminalSnafu { operator })?;
+            }
+            Mode::ConfirmDelete { id } => {
+                queue!(stdout, Print(format!("delete task({})? y/n", id)))
+                    .context(TerminalSnafu { operator })?;
+            }
+        }
+        std::io::Write::flush(stdout).context(TerminalSnafu { operator })
+    }
+}
+
+/// Runs the full-screen interface on the tasks of `repo`, all tasks when
+/// `None`, until the user quits.
+pub fn run(repo: Option<String>) -> Result<()> {
+    let conn = db::create_connection().context(DatabaseSnafu { cases: "open tui" })?;
+    db::ensure_table(&conn).context(DatabaseSnafu { cases: "open tui" })?;
+    let mut app = App::new(conn, repo)?;
+    // changes made by other processes, e.g. `todo done` in another terminal
+    let (tx, rx) = std::sync::mpsc::channel();
+    let _watcher = watch::watch(std::time::Duration::from_millis(500), move |_| {
+        let _ = tx.send(());
+    })
+    .context(DatabaseSnafu {
+        cases: "watch database",
+    })?;
+
+    let mut stdout = std::io::stdout();
+    let _screen = RawScreen::enter(&mut stdout).context(ScreenSnafu {})?;
+    app.render(&mut stdout)?;
+    loop {
+        let ready = crossterm::event::poll(std::time::Duration::from_millis(200)).context(
+            TerminalSnafu {
+                operator: "poll event",
+            },
+        )?;
+        if ready {
+            match crossterm::event::read().context(TerminalSnafu {
+                operator: "read event",
+            })? {
+                Event::Key(key) if key.is_press() => {
+                    if app.handle_key(key) {
+                        return Ok(());
+                    }
+                }
+                Event::Resize(_, _) => {}
+                _ => continue,
+            }
+        } else if rx.try_recv().is_ok() {
+            while rx.try_recv().is_ok() {}
+            app.reload(None);
+        } else {
+            continue;
+        }
+        app.render(&mut stdout)?;
+    }
+}
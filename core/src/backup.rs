@@ -0,0 +1,253 @@
+use chrono::Datelike;
+use snafu::{OptionExt, ResultExt, prelude::Snafu};
+
+use crate::db;
+
+#[derive(Debug, Snafu)]
+pub enum BackupError {
+    #[snafu(display("database error when {}", cases))]
+    Database {
+        source: db::DBError,
+        cases: &'static str,
+    },
+    #[snafu(display("failed to create todo root directory"))]
+    Root { source: crate::root_path::Error },
+    #[snafu(display("failed to access backup '{}'", path))]
+    Io {
+        source: std::io::Error,
+        path: String,
+    },
+    #[snafu(display("failed to copy database to '{}'", path))]
+    Copy {
+        source: rusqlite::Error,
+        path: String,
+    },
+    #[snafu(display("cannot find snapshot '{}'", name))]
+    SnapshotNotFound { name: String },
+}
+
+type Result<T> = std::result::Result<T, BackupError>;
+
+const SNAPSHOT_PREFIX: &str = "todo-";
+const SNAPSHOT_SUFFIX: &str = ".db";
+const SNAPSHOT_TIME_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+
+/// How many snapshots survive a rotation: the newest snapshot of each of the
+/// last `daily` days and of each of the last `weekly` weeks are kept.
+#[derive(Debug, Clone, Copy)]
+pub struct RotationPolicy {
+    pub daily: usize,
+    pub weekly: usize,
+}
+
+impl Default for RotationPolicy {
+    fn default() -> Self {
+        Self {
+            daily: 7,
+            weekly: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub name: String,
+    pub path: std::path::PathBuf,
+    pub time: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotSummary {
+    pub open: i64,
+    pub total: i64,
+}
+
+pub fn get_backup_folder() -> Result<String> {
+    let dir_path = format!(
+        "{}/backups",
+        crate::root_path::get_folder().context(RootSnafu {})?
+    );
+    std::fs::create_dir_all(&dir_path).context(IoSnafu {
+        path: dir_path.clone(),
+    })?;
+    Ok(dir_path)
+}
+
+fn parse_snapshot(path: std::path::PathBuf) -> Option<Snapshot> {
+    let name = path.file_name()?.to_str()?.to_owned();
+    let time = name
+        .strip_prefix(SNAPSHOT_PREFIX)?
+        .strip_suffix(SNAPSHOT_SUFFIX)?;
+    let time = chrono::NaiveDateTime::parse_from_str(time, SNAPSHOT_TIME_FORMAT).ok()?;
+    Some(Snapshot { name, path, time })
+}
+
+/// Lists the snapshots in the backup folder, oldest first.
+pub fn list_snapshots() -> Result<Vec<Snapshot>> {
+    let dir_path = get_backup_folder()?;
+    let entries = std::fs::read_dir(&dir_path).context(IoSnafu { path: &dir_path })?;
+    let mut snapshots = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| parse_snapshot(entry.path()))
+        .collect::<Vec<_>>();
+    snapshots.sort_by_key(|snapshot| snapshot.time);
+    Ok(snapshots)
+}
+
+/// Counts the tasks stored in a snapshot.
+pub fn summarize_snapshot(snapshot: &Snapshot) -> Result<SnapshotSummary> {
+    let cases = "summarize snapshot";
+    let conn =
+        db::open_connection(&snapshot.path.to_string_lossy()).context(DatabaseSnafu { cases })?;
+    let tasks = db::list_all_tasks(&conn).context(DatabaseSnafu { cases })?;
+    Ok(SnapshotSummary {
+        open: tasks
+            .iter()
+            .filter(|task| task.status == db::TaskStatus::Open)
+            .count() as i64,
+        total: tasks.len() as i64,
+    })
+}
+
+fn copy_database(
+    src: &rusqlite::Connection,
+    dst: &mut rusqlite::Connection,
+    path: &str,
+) -> Result<()> {
+    rusqlite::backup::Backup::new(src, dst)
+        .and_then(|backup| {
+            backup.run_to_completion(100, std::time::Duration::from_millis(10), None)
+        })
+        .context(CopySnafu { path })
+}
+
+/// Takes a snapshot of `conn` with the SQLite online backup API.
+pub fn create_snapshot(conn: &db::Connection) -> Result<Snapshot> {
+    let now = chrono::Local::now().naive_local();
+    let name = format!(
+        "{}{}{}",
+        SNAPSHOT_PREFIX,
+        now.format(SNAPSHOT_TIME_FORMAT),
+        SNAPSHOT_SUFFIX
+    );
+    let path = std::path::PathBuf::from(get_backup_folder()?).join(&name);
+    let path_str = path.to_string_lossy().to_string();
+    let mut dst = db::open_connection(&path_str).context(DatabaseSnafu {
+        cases: "create snapshot",
+    })?;
+    copy_database(conn.raw(), dst.raw_mut(), &path_str)?;
+    Ok(Snapshot {
+        name,
+        path,
+        time: now,
+    })
+}
+
+/// Returns the snapshots which are not kept by `policy`.
+fn outdated_snapshots(snapshots: &[Snapshot], policy: RotationPolicy) -> Vec<Snapshot> {
+    let mut days = std::collections::BTreeSet::new();
+    let mut weeks = std::collections::BTreeSet::new();
+    let mut outdated = Vec::new();
+    for snapshot in snapshots.iter().rev() {
+        let day = snapshot.time.date();
+        let week = day.iso_week();
+        let mut keep = false;
+        if days.len() < policy.daily && days.insert(day) {
+            keep = true;
+        }
+        if !weeks.contains(&week) && weeks.len() < policy.weekly {
+            weeks.insert(week);
+            keep = true;
+        }
+        if !keep {
+            outdated.push(snapshot.clone());
+        }
+    }
+    outdated
+}
+
+/// Deletes the snapshots which are not kept by `policy`.
+pub fn rotate_snapshots(policy: RotationPolicy) -> Result<Vec<Snapshot>> {
+    let outdated = outdated_snapshots(&list_snapshots()?, policy);
+    for snapshot in &outdated {
+        std::fs::remove_file(&snapshot.path).context(IoSnafu {
+            path: snapshot.path.to_string_lossy(),
+        })?;
+    }
+    Ok(outdated)
+}
+
+/// Takes a snapshot when the newest one is older than a day, then rotates.
+pub fn auto_backup(conn: &db::Connection, policy: RotationPolicy) -> Result<Option<Snapshot>> {
+    let now = chrono::Local::now().naive_local();
+    let is_recent = list_snapshots()?
+        .last()
+        .is_some_and(|latest| now - latest.time < chrono::TimeDelta::days(1));
+    if is_recent {
+        return Ok(None);
+    }
+    let snapshot = create_snapshot(conn)?;
+    rotate_snapshots(policy)?;
+    Ok(Some(snapshot))
+}
+
+/// Finds a snapshot by its file name, with or without the `.db` suffix.
+pub fn find_snapshot(name: &str) -> Result<Snapshot> {
+    let name = name.trim_end_matches(SNAPSHOT_SUFFIX);
+    list_snapshots()?
+        .into_iter()
+        .find(|snapshot| snapshot.name.trim_end_matches(SNAPSHOT_SUFFIX) == name)
+        .context(SnapshotNotFoundSnafu { name })
+}
+
+/// Replaces the content of `conn` with `snapshot`. The current state is
+/// snapshotted first so that a restore can be undone.
+pub fn restore_snapshot(conn: &mut db::Connection, snapshot: &Snapshot) -> Result<Snapshot> {
+    let current = create_snapshot(conn)?;
+    let path = snapshot.path.to_string_lossy().to_string();
+    let src = db::open_connection(&path).context(DatabaseSnafu {
+        cases: "restore snapshot",
+    })?;
+    copy_database(src.raw(), conn.raw_mut(), &path)?;
+    Ok(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(time: &str) -> Snapshot {
+        let name = format!("{}{}{}", SNAPSHOT_PREFIX, time, SNAPSHOT_SUFFIX);
+        parse_snapshot(std::path::PathBuf::from(name)).unwrap()
+    }
+
+    #[test]
+    fn test_outdated_snapshots() {
+        // 2025-01-06 is a monday
+        let snapshots = [
+            "20241220-090000.000",
+            "20241230-090000.000",
+            "20250101-090000.000",
+            "20250106-090000.000",
+            "20250106-180000.000",
+            "20250107-090000.000",
+        ]
+        .map(snapshot);
+        let policy = RotationPolicy {
+            daily: 2,
+            weekly: 2,
+        };
+        let outdated = outdated_snapshots(&snapshots, policy)
+            .into_iter()
+            .map(|snapshot| snapshot.name)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outdated,
+            vec![
+                "todo-20250106-090000.000.db",
+                "todo-20241230-090000.000.db",
+                "todo-20241220-090000.000.db",
+            ]
+        );
+    }
+}
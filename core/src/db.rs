@@ -1,4 +1,4 @@
-use snafu::{ResultExt, prelude::Snafu};
+use snafu::{OptionExt, ResultExt, prelude::Snafu};
 
 pub struct Connection(rusqlite::Connection);
 
@@ -9,9 +9,16 @@ impl Connection {
     fn prepare(&self, sql: &str) -> rusqlite::Result<rusqlite::Statement<'_>> {
         self.0.prepare(sql)
     }
+    pub(crate) fn raw(&self) -> &rusqlite::Connection {
+        &self.0
+    }
+    pub(crate) fn raw_mut(&mut self) -> &mut rusqlite::Connection {
+        &mut self.0
+    }
 }
 
 #[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
 pub enum DBError {
     #[snafu(display("failed to create todo root directory"))]
     Root { source: crate::root_path::Error },
@@ -32,19 +39,60 @@ pub enum DBError {
     },
     #[snafu(display("invalid database"))]
     InvalidDatabase {},
+    #[snafu(display("cannot find task '{}'", reference))]
+    TaskNotFound { reference: String },
+    #[snafu(display("task reference '{}' matches more than one task", reference))]
+    AmbiguousTask { reference: String },
+    #[snafu(display("database is encrypted, set TODO_PASSPHRASE or provide a keyfile"))]
+    KeyRequired {},
+    #[snafu(display("wrong passphrase for encrypted database"))]
+    WrongKey {},
+    #[snafu(display("encryption is not supported, rebuild with the 'encryption' feature"))]
+    EncryptionUnsupported {},
+    #[snafu(display("failed to read keyfile '{}'", path))]
+    ReadKeyfile {
+        source: std::io::Error,
+        path: String,
+    },
+    #[snafu(display("failed to replace database '{}'", db_path))]
+    ReplaceDatabase {
+        source: std::io::Error,
+        db_path: String,
+    },
 }
 
-type Result<T> = std::result::Result<T, DBError>;
+pub type Result<T> = std::result::Result<T, DBError>;
 
-pub fn create_connection() -> Result<Connection> {
-    let db_path = format!(
+pub fn get_db_path() -> Result<String> {
+    Ok(format!(
         "{}/todo.db",
         crate::root_path::get_folder().context(RootSnafu {})?
-    );
-    let conn = rusqlite::Connection::open(&db_path).context(ConnectSnafu { db_path })?;
+    ))
+}
+
+pub fn create_connection() -> Result<Connection> {
+    open_connection(&get_db_path()?)
+}
+
+/// Opens the database at `db_path`, unlocking it with the configured key
+/// when it is encrypted.
+pub fn open_connection(db_path: &str) -> Result<Connection> {
+    open_connection_with_key(db_path, crate::encryption::configured_key()?.as_deref())
+}
+
+pub fn open_connection_with_key(db_path: &str, key: Option<&str>) -> Result<Connection> {
+    let conn = rusqlite::Connection::open(db_path).context(ConnectSnafu { db_path })?;
+    crate::encryption::unlock(&conn, key)?;
     Ok(Connection(conn))
 }
 
+pub fn data_version(conn: &Connection) -> Result<i64> {
+    let sql = "PRAGMA data_version";
+    conn.0
+        .query_row(sql, [], |row| row.get(0))
+        .context(SqlSnafu { sql })
+}
+
 pub fn ensure_table(conn: &Connection) -> Result<()> {
     let sql = r##"
     CREATE TABLE IF NOT EXISTS todo
@@ -57,16 +105,125 @@ pub fn ensure_table(conn: &Connection) -> Result<()> {
     )
     "##;
     conn.execute(sql, []).context(SqlSnafu { sql })?;
+    migrate(conn)?;
     Ok(())
 }
 
+type Migration = fn(&Connection) -> Result<()>;
+
+/// Schema changes applied on top of the initial `todo` table. `user_version`
+/// records how many of them have already been applied to the database.
+const MIGRATIONS: &[Migration] = &[
+    add_uuid_column,
+    crate::sync::create_sync_tables,
+    crate::sync::create_sync_state_tables,
+    add_metadata_columns,
+    add_due_column,
+    add_source_column,
+    add_repo_column,
+    crate::link::create_link_table,
+];
+
+fn migrate(conn: &Connection) -> Result<()> {
+    let sql = "PRAGMA user_version";
+    let version: usize = conn
+        .0
+        .query_row(sql, [], |row| row.get(0))
+        .context(SqlSnafu { sql })?;
+    if version >= MIGRATIONS.len() {
+        return Ok(());
+    }
+
+    let sql = "BEGIN IMMEDIATE";
+    let tx =
+        rusqlite::Transaction::new_unchecked(&conn.0, rusqlite::TransactionBehavior::Immediate)
+            .context(SqlSnafu { sql })?;
+    // another process may have migrated the database while we were waiting for the lock
+    let sql = "PRAGMA user_version";
+    let version: usize = tx
+        .query_row(sql, [], |row| row.get(0))
+        .context(SqlSnafu { sql })?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        migration(conn)?;
+        let sql = "PRAGMA user_version";
+        tx.pragma_update(None, "user_version", index + 1)
+            .context(SqlSnafu { sql })?;
+    }
+    crate::sync::ensure_clock_triggers(conn)?;
+    let sql = "COMMIT";
+    tx.commit().context(SqlSnafu { sql })?;
+    Ok(())
+}
+
+fn add_uuid_column(conn: &Connection) -> Result<()> {
+    let sql = "ALTER TABLE todo ADD COLUMN uuid TEXT";
+    conn.execute(sql, []).context(SqlSnafu { sql })?;
+
+    let sql = "SELECT id FROM todo WHERE uuid IS NULL";
+    let ids = (|| -> rusqlite::Result<Vec<i64>> {
+        let mut stmt = conn.prepare(sql)?;
+        stmt.query_map([], |row| row.get(0))?.collect()
+    })()
+    .context(SqlSnafu { sql })?;
+    let sql = "UPDATE todo SET uuid = ?2 WHERE id = ?1";
+    for id in ids {
+        conn.execute(sql, rusqlite::params![id, new_uuid()])
+            .context(SqlSnafu { sql })?;
+    }
+
+    let sql = "CREATE UNIQUE INDEX IF NOT EXISTS todo_uuid ON todo (uuid)";
+    conn.execute(sql, []).context(SqlSnafu { sql })?;
+    Ok(())
+}
+
+fn add_metadata_columns(conn: &Connection) -> Result<()> {
+    for sql in [
+        "ALTER TABLE todo ADD COLUMN priority TEXT",
+        "ALTER TABLE todo ADD COLUMN project TEXT",
+        "ALTER TABLE todo ADD COLUMN tags TEXT NOT NULL DEFAULT ''",
+    ] {
+        conn.execute(sql, []).context(SqlSnafu { sql })?;
+    }
+    Ok(())
+}
+
+fn add_due_column(conn: &Connection) -> Result<()> {
+    let sql = "ALTER TABLE todo ADD COLUMN due_time TIMESTAMP";
+    conn.execute(sql, []).context(SqlSnafu { sql })?;
+    Ok(())
+}
+
+fn add_source_column(conn: &Connection) -> Result<()> {
+    let sql = "ALTER TABLE todo ADD COLUMN source TEXT";
+    conn.execute(sql, []).context(SqlSnafu { sql })?;
+    Ok(())
+}
+
+fn add_repo_column(conn: &Connection) -> Result<()> {
+    let sql = "ALTER TABLE todo ADD COLUMN repo TEXT";
+    conn.execute(sql, []).context(SqlSnafu { sql })?;
+    Ok(())
+}
+
+pub fn new_uuid() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
 pub fn insert_task(conn: &Connection, task: &str) -> Result<()> {
+    insert_task_in(conn, task, None)?;
+    Ok(())
+}
+
+/// Inserts a task belonging to the repository with the key `repo`, returns
+/// the id of the new task.
+pub fn insert_task_in(conn: &Connection, task: &str, repo: Option<&str>) -> Result<i64> {
     let sql = r##"
     INSERT INTO todo
-    (task) VALUES (?1)
+    (task, uuid, repo) VALUES (?1, ?2, ?3)
     "##;
-    conn.execute(sql, [&task]).context(SqlSnafu { sql })?;
-    Ok(())
+    conn.execute(sql, rusqlite::params![task, new_uuid(), repo])
+        .context(SqlSnafu { sql })?;
+    Ok(conn.raw().last_insert_rowid())
 }
 
 pub fn delete_task(conn: &Connection, id: i64) -> Result<()> {
@@ -101,6 +258,17 @@ pub fn done_task(conn: &Connection, id: i64) -> Result<()> {
     Ok(())
 }
 
+/// Runs `f` in a transaction, either all of its changes are kept or, when it
+/// fails, none.
+pub fn in_transaction<T>(conn: &Connection, f: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+    let sql = "BEGIN";
+    let tx = conn.0.unchecked_transaction().context(SqlSnafu { sql })?;
+    let value = f(conn)?;
+    let sql = "COMMIT";
+    tx.commit().context(SqlSnafu { sql })?;
+    Ok(value)
+}
+
 pub fn clean_outdate_task(conn: &Connection) -> Result<()> {
     let sql = r##"
         DELETE FROM todo
@@ -114,6 +282,7 @@ pub fn clean_outdate_task(conn: &Connection) -> Result<()> {
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct OpenTask {
     pub id: i64,
+    pub uuid: String,
     pub create_time: String,
     pub task: String,
 }
@@ -123,90 +292,422 @@ impl OpenTask {
             id: row.get(0)?,
             create_time: row.get(1)?,
             task: row.get(2)?,
+            uuid: row.get(3)?,
         })
     }
 }
 
 pub fn list_tasks(conn: &Connection) -> Result<Vec<OpenTask>> {
+    list_tasks_in(conn, None)
+}
+
+/// Lists the open tasks of the repository with the key `repo`, or of every
+/// repository when `repo` is `None`.
+pub fn list_tasks_in(conn: &Connection, repo: Option<&str>) -> Result<Vec<OpenTask>> {
     let sql = r##"
-        SELECT id, create_time, task FROM todo WHERE status = 'open'
+        SELECT id, create_time, task, uuid FROM todo
+        WHERE status = 'open' AND (?1 IS NULL OR repo = ?1)
     "##;
     let ret: Vec<OpenTask> = (|| -> rusqlite::Result<Vec<OpenTask>> {
         let mut stmt = conn.prepare(sql)?;
-        let ret = stmt
-            .query_map([], |row: &rusqlite::Row<'_>| OpenTask::new(row))?
-            .collect();
-        ret
+        stmt.query_map([repo], |row: &rusqlite::Row<'_>| OpenTask::new(row))?
+            .collect()
     })()
     .context(SqlSnafu { sql })?;
     Ok(ret)
 }
 
-#[derive(Debug, Clone)]
+/// Lists the distinct projects and tags of all tasks, sorted.
+pub fn list_labels(conn: &Connection) -> Result<Vec<String>> {
+    let sql = "SELECT project, tags FROM todo";
+    let rows = (|| -> rusqlite::Result<Vec<(Option<String>, String)>> {
+        let mut stmt = conn.prepare(sql)?;
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect()
+    })()
+    .context(SqlSnafu { sql })?;
+    let mut labels = std::collections::BTreeSet::new();
+    for (project, tags) in rows {
+        labels.extend(project.filter(|project| !project.is_empty()));
+        labels.extend(tags.split_whitespace().map(str::to_owned));
+    }
+    Ok(labels.into_iter().collect())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TaskStatus {
+    #[default]
     Open,
     Closed,
     Deleted,
 }
 
-#[derive(Debug, Clone)]
+impl TaskStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::Open => "open",
+            TaskStatus::Closed => "closed",
+            TaskStatus::Deleted => "deleted",
+        }
+    }
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "open" => Some(TaskStatus::Open),
+            "closed" => Some(TaskStatus::Closed),
+            "deleted" => Some(TaskStatus::Deleted),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Task {
+    #[serde(default)]
     pub id: i64,
+    #[serde(default)]
+    pub uuid: String,
     pub create_time: String,
+    #[serde(default)]
     pub finished_time: Option<String>,
     pub task: String,
     pub status: TaskStatus,
+    /// 'A' is the most important, like in todo.txt
+    #[serde(default)]
+    pub priority: Option<String>,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub due_time: Option<String>,
+    /// `path:line` of the code comment the task was scanned from
+    #[serde(default)]
+    pub source: Option<String>,
+    /// key of the git repository the task belongs to, see `repo::Repository`
+    #[serde(default)]
+    pub repo: Option<String>,
+}
+
+impl From<Task> for OpenTask {
+    fn from(task: Task) -> Self {
+        Self {
+            id: task.id,
+            uuid: task.uuid,
+            create_time: task.create_time,
+            task: task.task,
+        }
+    }
 }
 
 #[derive(Clone)]
 struct TaskImpl {
     pub id: i64,
+    pub uuid: String,
     pub create_time: String,
     pub finished_time: Option<String>,
     pub task: String,
     pub status: String,
+    pub priority: Option<String>,
+    pub project: Option<String>,
+    pub tags: String,
+    pub due_time: Option<String>,
+    pub source: Option<String>,
+    pub repo: Option<String>,
+}
+
+/// Columns read by `TaskImpl::new`, shared by every query returning `Task`.
+macro_rules! task_columns {
+    () => {
+        "id, create_time, finished_time, status, task, uuid, priority, project, tags, due_time, source, repo"
+    };
 }
 
 impl TaskImpl {
     fn new(row: &rusqlite::Row) -> rusqlite::Result<Self> {
         Ok(Self {
-            id: row.get(0)?,
-            create_time: row.get(1)?,
-            finished_time: row.get(2)?,
-            status: row.get(3)?,
-            task: row.get(4)?,
+            id: row.get("id")?,
+            create_time: row.get("create_time")?,
+            finished_time: row.get("finished_time")?,
+            status: row.get("status")?,
+            task: row.get("task")?,
+            uuid: row.get("uuid")?,
+            priority: row.get("priority")?,
+            project: row.get("project")?,
+            tags: row.get("tags")?,
+            due_time: row.get("due_time")?,
+            source: row.get("source")?,
+            repo: row.get("repo")?,
         })
     }
-    fn to_task(self: &Self) -> Result<Task> {
+    fn to_task(&self) -> Result<Task> {
         Ok(Task {
             id: self.id,
+            uuid: self.uuid.clone(),
             create_time: self.create_time.clone(),
             finished_time: self.finished_time.clone(),
-            status: match self.status.as_str() {
-                "open" => TaskStatus::Open,
-                "closed" => TaskStatus::Closed,
-                "deleted" => TaskStatus::Deleted,
-                _ => panic!(),
-            },
+            status: TaskStatus::parse(&self.status).context(InvalidDatabaseSnafu {})?,
             task: self.task.clone(),
+            priority: self.priority.clone(),
+            project: self.project.clone(),
+            tags: self.tags.split_whitespace().map(str::to_owned).collect(),
+            due_time: self.due_time.clone(),
+            source: self.source.clone(),
+            repo: self.repo.clone(),
         })
     }
 }
 
-pub fn list_all_tasks(conn: &Connection) -> Result<Vec<Task>> {
-    let sql = r##"
-        SELECT id, create_time, finished_time, status, task FROM todo
-    "##;
-
+fn query_tasks<P: rusqlite::Params>(
+    conn: &Connection,
+    sql: &'static str,
+    params: P,
+) -> Result<Vec<Task>> {
     let mut stmt = conn.prepare(sql).context(SqlSnafu { sql })?;
     let ret = stmt
-        .query_map([], |row: &rusqlite::Row<'_>| TaskImpl::new(row))
+        .query_map(params, |row: &rusqlite::Row<'_>| TaskImpl::new(row))
         .context(SqlSnafu { sql })?
         .collect::<rusqlite::Result<Vec<TaskImpl>>>()
         .context(SqlSnafu { sql })?;
-    let ret = ret
-        .iter()
-        .map(|task_impl| TaskImpl::to_task(task_impl))
-        .collect();
-    ret
+    ret.iter().map(TaskImpl::to_task).collect()
+}
+
+pub fn list_all_tasks(conn: &Connection) -> Result<Vec<Task>> {
+    list_all_tasks_in(conn, None)
+}
+
+/// Like `list_all_tasks`, restricted to a repository unless `repo` is `None`.
+pub fn list_all_tasks_in(conn: &Connection, repo: Option<&str>) -> Result<Vec<Task>> {
+    let sql = concat!(
+        "SELECT ",
+        task_columns!(),
+        " FROM todo WHERE ?1 IS NULL OR repo = ?1"
+    );
+    query_tasks(conn, sql, [repo])
+}
+
+pub fn get_task(conn: &Connection, id: i64) -> Result<Option<Task>> {
+    let sql = concat!("SELECT ", task_columns!(), " FROM todo WHERE id = ?1");
+    Ok(query_tasks(conn, sql, [id])?.pop())
+}
+
+pub fn get_task_by_uuid(conn: &Connection, uuid: &str) -> Result<Option<Task>> {
+    let sql = concat!("SELECT ", task_columns!(), " FROM todo WHERE uuid = ?1");
+    Ok(query_tasks(conn, sql, [uuid])?.pop())
+}
+
+/// Inserts a task with all of its fields but the id, e.g. when importing, and
+/// returns the id assigned by the database. A new uuid is generated when
+/// `task.uuid` is empty.
+pub fn insert_full_task(conn: &Connection, task: &Task) -> Result<i64> {
+    let sql = r##"
+    INSERT INTO todo
+    (uuid, create_time, finished_time, task, status, priority, project, tags, due_time, source,
+        repo)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+    "##;
+    let uuid = match task.uuid.is_empty() {
+        true => new_uuid(),
+        false => task.uuid.clone(),
+    };
+    conn.execute(
+        sql,
+        rusqlite::params![
+            uuid,
+            task.create_time,
+            task.finished_time,
+            task.task,
+            task.status.as_str(),
+            task.priority,
+            task.project,
+            task.tags.join(" "),
+            task.due_time,
+            task.source,
+            task.repo
+        ],
+    )
+    .context(SqlSnafu { sql })?;
+    Ok(conn.raw().last_insert_rowid())
+}
+
+/// Overwrites every field of the task identified by `task.uuid`.
+pub fn update_full_task(conn: &Connection, task: &Task) -> Result<()> {
+    let sql = r##"
+    UPDATE todo
+    SET create_time = ?2, finished_time = ?3, task = ?4, status = ?5,
+        priority = ?6, project = ?7, tags = ?8, due_time = ?9, source = ?10, repo = ?11
+    WHERE uuid = ?1
+    "##;
+    conn.execute(
+        sql,
+        rusqlite::params![
+            task.uuid,
+            task.create_time,
+            task.finished_time,
+            task.task,
+            task.status.as_str(),
+            task.priority,
+            task.project,
+            task.tags.join(" "),
+            task.due_time,
+            task.source,
+            task.repo
+        ],
+    )
+    .context(SqlSnafu { sql })?;
+    Ok(())
+}
+
+/// Removes a task from the database, unlike `delete_task` which only marks it
+/// as deleted.
+pub fn remove_task(conn: &Connection, uuid: &str) -> Result<()> {
+    let sql = "DELETE FROM todo WHERE uuid = ?1";
+    conn.execute(sql, [uuid]).context(SqlSnafu { sql })?;
+    Ok(())
+}
+
+/// Finds the task whose uuid equals or starts with `prefix`.
+pub fn find_task_by_uuid(conn: &Connection, prefix: &str) -> Result<Task> {
+    let sql = concat!(
+        "SELECT ",
+        task_columns!(),
+        " FROM todo WHERE uuid LIKE ?1 ESCAPE '\\' LIMIT 2"
+    );
+    let pattern = prefix
+        .to_lowercase()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+        + "%";
+    let mut tasks = query_tasks(conn, sql, [pattern])?;
+    match tasks.len() {
+        0 => TaskNotFoundSnafu { reference: prefix }.fail(),
+        1 => Ok(tasks.remove(0)),
+        _ => AmbiguousTaskSnafu { reference: prefix }.fail(),
+    }
+}
+
+/// Uuid prefixes shorter than this could too easily match the wrong task.
+const MIN_UUID_PREFIX: usize = 8;
+
+/// Resolves a user supplied task reference, either the integer id or the
+/// (prefix of the) uuid of the task. A number is only ever an id, a prefix
+/// needs at least 8 hex digits or a dash.
+pub fn resolve_task(conn: &Connection, reference: &str) -> Result<Task> {
+    if let Ok(id) = reference.parse::<i64>() {
+        return get_task(conn, id)?.context(TaskNotFoundSnafu { reference });
+    }
+    let hex_digits = reference.chars().filter(char::is_ascii_hexdigit).count();
+    if hex_digits < MIN_UUID_PREFIX && !reference.contains('-') {
+        return TaskNotFoundSnafu { reference }.fail();
+    }
+    find_task_by_uuid(conn, reference)
+}
+
+#[cfg(test)]
+pub(crate) fn memory_connection() -> Connection {
+    let conn = Connection(rusqlite::Connection::open_in_memory().unwrap());
+    ensure_table(&conn).unwrap();
+    conn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_task() {
+        let conn = memory_connection();
+        insert_task(&conn, "a").unwrap();
+        insert_task(&conn, "b").unwrap();
+        let tasks = list_all_tasks(&conn).unwrap();
+
+        assert_eq!(resolve_task(&conn, "2").unwrap().task, "b");
+        assert_eq!(resolve_task(&conn, &tasks[0].uuid).unwrap().task, "a");
+        assert_eq!(resolve_task(&conn, &tasks[1].uuid[..9]).unwrap().task, "b");
+        assert_eq!(resolve_task(&conn, &tasks[1].uuid[..8]).unwrap().task, "b");
+        assert!(matches!(
+            resolve_task(&conn, ""),
+            Err(DBError::TaskNotFound { .. })
+        ));
+        assert!(matches!(
+            resolve_task(&conn, &tasks[1].uuid[..7]),
+            Err(DBError::TaskNotFound { .. })
+        ));
+        assert!(matches!(
+            resolve_task(&conn, "-3"),
+            Err(DBError::TaskNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_resolve_numeric_reference() {
+        let conn = memory_connection();
+        for uuid in [
+            "12345678-0000-4000-8000-000000000001",
+            "abcdef01-0000-4000-8000-000000000002",
+            "abcdef01-0000-4000-8000-000000000003",
+        ] {
+            let task = Task {
+                uuid: uuid.to_owned(),
+                create_time: "2025-01-01 00:00:00".to_owned(),
+                task: uuid.to_owned(),
+                ..Default::default()
+            };
+            insert_full_task(&conn, &task).unwrap();
+        }
+        // numbers are ids, even when a uuid starts with them
+        assert_eq!(resolve_task(&conn, "1").unwrap().uuid[..8], *"12345678");
+        for reference in ["12", "12345678"] {
+            assert!(matches!(
+                resolve_task(&conn, reference),
+                Err(DBError::TaskNotFound { .. })
+            ));
+        }
+        assert_eq!(resolve_task(&conn, "12345678-").unwrap().id, 1);
+        assert!(matches!(
+            resolve_task(&conn, "abcdef01"),
+            Err(DBError::AmbiguousTask { .. })
+        ));
+    }
+
+    #[test]
+    fn test_in_transaction() {
+        let conn = memory_connection();
+        insert_task(&conn, "a").unwrap();
+        insert_task(&conn, "b").unwrap();
+        let result = in_transaction(&conn, |conn| {
+            done_task(conn, 1)?;
+            resolve_task(conn, "3").map(|_| ())
+        });
+        assert!(result.is_err());
+        assert_eq!(list_tasks(&conn).unwrap().len(), 2);
+
+        in_transaction(&conn, |conn| {
+            [1, 2].iter().try_for_each(|id| done_task(conn, *id))
+        })
+        .unwrap();
+        assert!(list_tasks(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_list_labels() {
+        let conn = memory_connection();
+        insert_task(&conn, "a").unwrap();
+        for (project, tags) in [("home", vec!["urgent", "later"]), ("work", vec!["urgent"])] {
+            let task = Task {
+                uuid: project.to_owned(),
+                create_time: "2025-01-01 00:00:00".to_owned(),
+                task: "b".to_owned(),
+                project: Some(project.to_owned()),
+                tags: tags.into_iter().map(str::to_owned).collect(),
+                ..Default::default()
+            };
+            insert_full_task(&conn, &task).unwrap();
+        }
+        assert_eq!(
+            list_labels(&conn).unwrap(),
+            vec!["home", "later", "urgent", "work"]
+        );
+    }
 }
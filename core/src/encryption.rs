@@ -0,0 +1,177 @@
+use snafu::ResultExt;
+
+use crate::db::{self, ConnectSnafu, ReadKeyfileSnafu, RootSnafu, SqlSnafu};
+
+/// Environment variable holding the passphrase of an encrypted database.
+pub const PASSPHRASE_ENV: &str = "TODO_PASSPHRASE";
+/// Environment variable pointing to a file containing the passphrase.
+pub const KEYFILE_ENV: &str = "TODO_KEYFILE";
+/// Keyfile used when neither environment variable is set.
+pub const DEFAULT_KEYFILE: &str = "todo.key";
+
+pub fn is_supported() -> bool {
+    cfg!(feature = "encryption")
+}
+
+fn read_keyfile(path: &str) -> db::Result<String> {
+    let content = std::fs::read_to_string(path).context(ReadKeyfileSnafu { path })?;
+    Ok(content.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+/// Looks up the passphrase from `TODO_PASSPHRASE`, the file named by
+/// `TODO_KEYFILE` or `todo.key` in the todo folder, in this order.
+pub fn configured_key() -> db::Result<Option<String>> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(Some(passphrase));
+    }
+    if let Ok(path) = std::env::var(KEYFILE_ENV) {
+        return read_keyfile(&path).map(Some);
+    }
+    let path = format!(
+        "{}/{}",
+        crate::root_path::get_folder().context(RootSnafu {})?,
+        DEFAULT_KEYFILE
+    );
+    if std::path::Path::new(&path).exists() {
+        return read_keyfile(&path).map(Some);
+    }
+    Ok(None)
+}
+
+fn is_not_a_database(error: &rusqlite::Error) -> bool {
+    matches!(
+        error,
+        rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error {
+                code: rusqlite::ErrorCode::NotADatabase,
+                ..
+            },
+            _
+        )
+    )
+}
+
+/// Unlocks `conn` with `key` and checks that the database can be read.
+/// Without the 'encryption' feature the key is ignored, so a configured key
+/// only fails for a database that is actually encrypted.
+pub(crate) fn unlock(conn: &rusqlite::Connection, key: Option<&str>) -> db::Result<()> {
+    let key = key.filter(|_| is_supported());
+    if let Some(key) = key {
+        let sql = "PRAGMA key";
+        conn.pragma_update(None, "key", key)
+            .context(SqlSnafu { sql })?;
+    }
+    let sql = "SELECT COUNT(*) FROM sqlite_master";
+    match conn.query_row(sql, [], |row| row.get::<_, i64>(0)) {
+        Ok(_) => Ok(()),
+        Err(e) if is_not_a_database(&e) => match key {
+            _ if !is_supported() => db::EncryptionUnsupportedSnafu {}.fail(),
+            Some(_) => db::WrongKeySnafu {}.fail(),
+            None => db::KeyRequiredSnafu {}.fail(),
+        },
+        Err(e) => Err(e).context(SqlSnafu { sql }),
+    }
+}
+
+/// Re-writes the database at `db_path` through `sqlcipher_export` into a
+/// sibling file which then replaces the original one.
+fn export(db_path: &str, from_key: Option<&str>, to_key: &str) -> db::Result<()> {
+    if !is_supported() {
+        return db::EncryptionUnsupportedSnafu {}.fail();
+    }
+    let conn = rusqlite::Connection::open(db_path).context(ConnectSnafu { db_path })?;
+    unlock(&conn, from_key)?;
+
+    let export_path = format!("{}.export", db_path);
+    let _ = std::fs::remove_file(&export_path);
+    let sql = "ATTACH DATABASE ?1 AS export KEY ?2";
+    conn.execute(sql, [&export_path, to_key])
+        .context(SqlSnafu { sql })?;
+    let sql = "SELECT sqlcipher_export('export')";
+    conn.query_row(sql, [], |_| Ok(()))
+        .context(SqlSnafu { sql })?;
+    let sql = "PRAGMA user_version";
+    let version: i64 = conn
+        .query_row(sql, [], |row| row.get(0))
+        .context(SqlSnafu { sql })?;
+    conn.pragma_update(
+        Some(rusqlite::DatabaseName::Attached("export")),
+        "user_version",
+        version,
+    )
+    .context(SqlSnafu { sql })?;
+    let sql = "DETACH DATABASE export";
+    conn.execute(sql, []).context(SqlSnafu { sql })?;
+    // a WAL left next to the new file would be replayed into it
+    let sql = "PRAGMA wal_checkpoint(TRUNCATE)";
+    conn.query_row(sql, [], |_| Ok(()))
+        .context(SqlSnafu { sql })?;
+    drop(conn);
+    for suffix in ["-wal", "-shm", "-journal"] {
+        match std::fs::remove_file(format!("{}{}", db_path, suffix)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(e).context(db::ReplaceDatabaseSnafu { db_path });
+            }
+            _ => {}
+        }
+    }
+
+    std::fs::rename(&export_path, db_path).context(db::ReplaceDatabaseSnafu { db_path })?;
+    Ok(())
+}
+
+/// Encrypts the plaintext database at `db_path` with `key`.
+pub fn encrypt_database(db_path: &str, key: &str) -> db::Result<()> {
+    export(db_path, None, key)
+}
+
+/// Decrypts the database at `db_path` which is encrypted with `key`.
+pub fn decrypt_database(db_path: &str, key: &str) -> db::Result<()> {
+    export(db_path, Some(key), "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(feature = "encryption"))]
+    fn test_key_without_encryption() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        unlock(&conn, Some("passphrase")).unwrap();
+        assert!(matches!(
+            encrypt_database("unused.db", "passphrase"),
+            Err(db::DBError::EncryptionUnsupported {})
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "encryption")]
+    fn test_encrypt_and_decrypt() {
+        let temp = crate::test_support::TempDb::new("crypt");
+        let db_path = temp.path();
+        {
+            let conn = db::open_connection_with_key(db_path, None).unwrap();
+            db::ensure_table(&conn).unwrap();
+            db::insert_task(&conn, "secret").unwrap();
+        }
+
+        encrypt_database(db_path, "passphrase").unwrap();
+        assert!(matches!(
+            db::open_connection_with_key(db_path, None),
+            Err(db::DBError::KeyRequired {})
+        ));
+        assert!(matches!(
+            db::open_connection_with_key(db_path, Some("wrong")),
+            Err(db::DBError::WrongKey {})
+        ));
+        let conn = db::open_connection_with_key(db_path, Some("passphrase")).unwrap();
+        db::ensure_table(&conn).unwrap();
+        assert_eq!(db::list_tasks(&conn).unwrap()[0].task, "secret");
+        drop(conn);
+
+        decrypt_database(db_path, "passphrase").unwrap();
+        let conn = db::open_connection_with_key(db_path, None).unwrap();
+        assert_eq!(db::list_tasks(&conn).unwrap()[0].task, "secret");
+    }
+}
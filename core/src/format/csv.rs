@@ -0,0 +1,107 @@
+//! RFC 4180 CSV with a header row and a selectable set of columns.
+
+use crate::db;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Id,
+    Uuid,
+    Status,
+    CreateTime,
+    FinishedTime,
+    DueTime,
+    Task,
+    Priority,
+    Project,
+    Tags,
+}
+
+impl Column {
+    pub const DEFAULT: &[Column] = &[
+        Column::Id,
+        Column::Status,
+        Column::CreateTime,
+        Column::FinishedTime,
+        Column::Task,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Id => "id",
+            Column::Uuid => "uuid",
+            Column::Status => "status",
+            Column::CreateTime => "create_time",
+            Column::FinishedTime => "finished_time",
+            Column::DueTime => "due_time",
+            Column::Task => "task",
+            Column::Priority => "priority",
+            Column::Project => "project",
+            Column::Tags => "tags",
+        }
+    }
+
+    fn value(&self, task: &db::Task) -> String {
+        match self {
+            Column::Id => task.id.to_string(),
+            Column::Uuid => task.uuid.clone(),
+            Column::Status => task.status.as_str().to_owned(),
+            Column::CreateTime => task.create_time.clone(),
+            Column::FinishedTime => task.finished_time.clone().unwrap_or_default(),
+            Column::DueTime => task.due_time.clone().unwrap_or_default(),
+            Column::Task => task.task.clone(),
+            Column::Priority => task.priority.clone().unwrap_or_default(),
+            Column::Project => task.project.clone().unwrap_or_default(),
+            Column::Tags => task.tags.join(" "),
+        }
+    }
+}
+
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn record(fields: impl Iterator<Item = String>) -> String {
+    fields
+        .map(|field| quote(&field))
+        .collect::<Vec<_>>()
+        .join(",")
+        + "\r\n"
+}
+
+pub fn export(tasks: &[db::Task], columns: &[Column]) -> String {
+    let header = record(columns.iter().map(|column| column.name().to_owned()));
+    tasks.iter().fold(header, |content, task| {
+        content + &record(columns.iter().map(|column| column.value(task)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::stored_tasks;
+
+    #[test]
+    fn test_export() {
+        let mut tasks = stored_tasks(&[
+            ("plain", db::TaskStatus::Open),
+            ("say \"hi\", then\nleave", db::TaskStatus::Closed),
+        ]);
+        tasks[0].tags = vec!["a".to_owned(), "b".to_owned()];
+        let columns = [
+            Column::Id,
+            Column::Status,
+            Column::Task,
+            Column::Project,
+            Column::Tags,
+        ];
+        assert_eq!(
+            export(&tasks, &columns),
+            "id,status,task,project,tags\r\n1,open,plain,,a b\r\n\
+             2,closed,\"say \"\"hi\"\", then\nleave\",,\r\n"
+        );
+    }
+}
@@ -0,0 +1,367 @@
+//! RFC 5545 calendars of VTODO components.
+//!
+//! The first line of the task text is the SUMMARY and the following lines
+//! the DESCRIPTION. Priorities 'A' to 'I' map to PRIORITY 1 to 9, lower
+//! priorities are exported as 9. Task times are local, CREATED and COMPLETED
+//! are converted to UTC as the RFC requires while DUE stays a floating time.
+
+use chrono::TimeZone;
+use snafu::OptionExt;
+
+use crate::db;
+use crate::format::{Result, SyntaxSnafu};
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+const UTC_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+const FLOATING_FORMAT: &str = "%Y%m%dT%H%M%S";
+const DATE_FORMAT: &str = "%Y%m%d";
+/// Maximum length of a content line in octets, without the line break.
+const LINE_LIMIT: usize = 75;
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Splits `line` into chunks of at most 75 octets, never inside a character.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > LINE_LIMIT {
+            folded.push_str("\r\n ");
+            // the leading space counts towards the limit of the next line
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// Joins folded lines, returning each content line with its line number.
+fn unfold(content: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some((_, last))) => last.push_str(continuation),
+            _ if line.is_empty() => {}
+            _ => lines.push((index + 1, line.to_owned())),
+        }
+    }
+    lines
+}
+
+/// Splits a content line into its upper case name and its value, parameters
+/// such as `VALUE=DATE` or `TZID` are not needed to read the value.
+fn split_property(line: &str) -> Option<(String, &str)> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(index, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(index),
+        _ => None,
+    })?;
+    let name = line[..colon].split(';').next()?.to_ascii_uppercase();
+    Some((name, &line[colon + 1..]))
+}
+
+fn to_utc(time: &str) -> Option<String> {
+    let time = chrono::NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok()?;
+    let time = chrono::Local.from_local_datetime(&time).earliest()?;
+    Some(
+        time.with_timezone(&chrono::Utc)
+            .format(UTC_FORMAT)
+            .to_string(),
+    )
+}
+
+fn to_floating(time: &str) -> Option<String> {
+    let time = chrono::NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok()?;
+    Some(time.format(FLOATING_FORMAT).to_string())
+}
+
+/// Parses DATE and DATE-TIME values into local times, times with a TZID are
+/// read as local times.
+pub(crate) fn parse_time(value: &str) -> Option<String> {
+    let time = if let Some(utc) = value.strip_suffix('Z') {
+        let time = chrono::NaiveDateTime::parse_from_str(utc, FLOATING_FORMAT).ok()?;
+        chrono::Utc
+            .from_utc_datetime(&time)
+            .with_timezone(&chrono::Local)
+            .naive_local()
+    } else if value.contains('T') {
+        chrono::NaiveDateTime::parse_from_str(value, FLOATING_FORMAT).ok()?
+    } else {
+        chrono::NaiveDate::parse_from_str(value, DATE_FORMAT)
+            .ok()?
+            .and_hms_opt(0, 0, 0)?
+    };
+    Some(time.format(TIME_FORMAT).to_string())
+}
+
+fn priority_number(priority: &str) -> Option<u8> {
+    let letter = *priority.as_bytes().first()?;
+    letter
+        .is_ascii_uppercase()
+        .then(|| (letter - b'A' + 1).min(9))
+}
+
+fn priority_letter(number: &str) -> Option<String> {
+    match number.trim().parse::<u8>() {
+        Ok(number @ 1..=9) => Some(((b'A' + number - 1) as char).to_string()),
+        _ => None,
+    }
+}
+
+fn serialize_task(task: &db::Task, stamp: &str) -> String {
+    let mut lines = vec![
+        "BEGIN:VTODO".to_owned(),
+        format!("UID:{}", escape(&task.uuid)),
+        format!("DTSTAMP:{}", stamp),
+    ];
+    let (summary, description) = match task.task.split_once('\n') {
+        Some((summary, description)) => (summary, Some(description)),
+        None => (task.task.as_str(), None),
+    };
+    lines.push(format!("SUMMARY:{}", escape(summary)));
+    if let Some(description) = description {
+        lines.push(format!("DESCRIPTION:{}", escape(description)));
+    }
+    lines.push(format!(
+        "STATUS:{}",
+        match task.status {
+            db::TaskStatus::Open => "NEEDS-ACTION",
+            db::TaskStatus::Closed => "COMPLETED",
+            db::TaskStatus::Deleted => "CANCELLED",
+        }
+    ));
+    if let Some(created) = to_utc(&task.create_time) {
+        lines.push(format!("CREATED:{}", created));
+    }
+    if let Some(completed) = task.finished_time.as_deref().and_then(to_utc) {
+        lines.push(format!("COMPLETED:{}", completed));
+    }
+    if let Some(due) = task.due_time.as_deref().and_then(to_floating) {
+        lines.push(format!("DUE:{}", due));
+    }
+    if let Some(priority) = task.priority.as_deref().and_then(priority_number) {
+        lines.push(format!("PRIORITY:{}", priority));
+    }
+    lines.push("END:VTODO".to_owned());
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+pub fn export(tasks: &[db::Task]) -> String {
+    let stamp = chrono::Utc::now().format(UTC_FORMAT).to_string();
+    let mut content = fold("BEGIN:VCALENDAR") + &fold("VERSION:2.0");
+    content += &fold("PRODID:-//todo//todo_core//EN");
+    for task in tasks {
+        content += &serialize_task(task, &stamp);
+    }
+    content + &fold("END:VCALENDAR")
+}
+
+#[derive(Default)]
+struct Component {
+    line: usize,
+    uuid: String,
+    summary: Option<String>,
+    description: Option<String>,
+    status: Option<db::TaskStatus>,
+    created: Option<String>,
+    stamp: Option<String>,
+    completed: Option<String>,
+    due: Option<String>,
+    priority: Option<String>,
+}
+
+impl Component {
+    fn into_task(self) -> Result<db::Task> {
+        let summary = self.summary.context(SyntaxSnafu {
+            line: self.line,
+            reason: "VTODO without SUMMARY",
+        })?;
+        let task = match self.description {
+            Some(description) => format!("{}\n{}", summary, description),
+            None => summary,
+        };
+        let status = self.status.unwrap_or(match self.completed {
+            Some(_) => db::TaskStatus::Closed,
+            None => db::TaskStatus::Open,
+        });
+        let create_time = self
+            .created
+            .or(self.stamp)
+            .unwrap_or_else(|| chrono::Local::now().format(TIME_FORMAT).to_string());
+        Ok(db::Task {
+            uuid: self.uuid,
+            create_time,
+            finished_time: self.completed,
+            task,
+            status,
+            priority: self.priority,
+            due_time: self.due,
+            ..Default::default()
+        })
+    }
+}
+
+/// Parses every VTODO of `content`, other components are ignored.
+pub fn parse(content: &str) -> Result<Vec<db::Task>> {
+    let mut tasks = Vec::new();
+    let mut component: Option<Component> = None;
+    // components nested in the VTODO, like VALARM, whose properties are not
+    // the task's
+    let mut depth = 0;
+    for (line, text) in unfold(content) {
+        let (name, value) = split_property(&text).context(SyntaxSnafu {
+            line,
+            reason: "content line without ':'",
+        })?;
+        let invalid_time = || SyntaxSnafu {
+            line,
+            reason: format!("invalid {} '{}'", name, value),
+        };
+        match (name.as_str(), component.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VTODO") => {
+                component = Some(Component {
+                    line,
+                    ..Default::default()
+                });
+            }
+            ("BEGIN", Some(_)) => depth += 1,
+            ("END", Some(_)) if depth > 0 => depth -= 1,
+            (_, Some(_)) if depth > 0 => {}
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VTODO") => {
+                tasks.push(component.take().unwrap().into_task()?);
+            }
+            ("UID", Some(todo)) => todo.uuid = unescape(value),
+            ("SUMMARY", Some(todo)) => todo.summary = Some(unescape(value)),
+            ("DESCRIPTION", Some(todo)) => todo.description = Some(unescape(value)),
+            ("STATUS", Some(todo)) => {
+                todo.status = Some(match value.to_ascii_uppercase().as_str() {
+                    "COMPLETED" => db::TaskStatus::Closed,
+                    "CANCELLED" => db::TaskStatus::Deleted,
+                    _ => db::TaskStatus::Open,
+                })
+            }
+            ("CREATED", Some(todo)) => {
+                todo.created = Some(parse_time(value).with_context(invalid_time)?)
+            }
+            ("DTSTAMP", Some(todo)) => {
+                todo.stamp = Some(parse_time(value).with_context(invalid_time)?)
+            }
+            ("COMPLETED", Some(todo)) => {
+                todo.completed = Some(parse_time(value).with_context(invalid_time)?)
+            }
+            ("DUE", Some(todo)) => todo.due = Some(parse_time(value).with_context(invalid_time)?),
+            ("PRIORITY", Some(todo)) => todo.priority = priority_letter(value),
+            _ => {}
+        }
+    }
+    match component {
+        Some(todo) => SyntaxSnafu {
+            line: todo.line,
+            reason: "VTODO without END",
+        }
+        .fail(),
+        None => Ok(tasks),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::stored_tasks;
+
+    #[test]
+    fn test_fold() {
+        let line = format!("SUMMARY:{}", "ä".repeat(50));
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|line| line.len() <= LINE_LIMIT));
+        assert_eq!(unfold(&folded), vec![(1, line)]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let long = "long ".repeat(40);
+        let mut tasks = stored_tasks(&[
+            (
+                "release 1.0; tag, publish\nsee \\docs\\",
+                db::TaskStatus::Open,
+            ),
+            (&long, db::TaskStatus::Closed),
+            ("dropped", db::TaskStatus::Deleted),
+        ]);
+        tasks[0].priority = Some("B".to_owned());
+        tasks[0].due_time = Some("2025-03-01 17:00:00".to_owned());
+
+        let content = export(&tasks);
+        // the first line is the summary, the others the description
+        assert!(content.contains("SUMMARY:release 1.0\\; tag\\, publish\r\n"));
+        assert!(content.contains("DESCRIPTION:see \\\\docs\\\\\r\n"));
+        assert!(content.contains("STATUS:CANCELLED\r\n"));
+        let parsed = parse(&content).unwrap();
+        for (parsed, task) in parsed.into_iter().zip(tasks) {
+            assert_eq!(parsed, db::Task { id: 0, ..task });
+        }
+    }
+
+    #[test]
+    fn test_nested_component() {
+        let content = "BEGIN:VTODO\r\nUID:1\r\nSUMMARY:renew passport\r\n\
+            BEGIN:VALARM\r\nACTION:DISPLAY\r\nSUMMARY:reminder\r\n\
+            DESCRIPTION:passport expires\r\nTRIGGER:-P1D\r\nEND:VALARM\r\n\
+            STATUS:COMPLETED\r\nEND:VTODO\r\n";
+        let tasks = parse(content).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].task, "renew passport");
+        assert_eq!(tasks[0].status, db::TaskStatus::Closed);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let content = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:1\r\nEND:VTODO\r\n";
+        assert!(matches!(
+            parse(content),
+            Err(crate::format::FormatError::Syntax { line: 2, .. })
+        ));
+        let content = "BEGIN:VTODO\nSUMMARY:a\nCREATED:yesterday\nEND:VTODO\n";
+        assert!(matches!(
+            parse(content),
+            Err(crate::format::FormatError::Syntax { line: 3, .. })
+        ));
+    }
+}
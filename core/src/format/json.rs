@@ -0,0 +1,121 @@
+use snafu::ResultExt;
+
+use crate::db;
+use crate::format::{JsonSnafu, Result, UnsupportedVersionSnafu};
+
+/// Version of the interchange document. Fields added to `Task` later must be
+/// optional when reading so that older documents keep importing, bump the
+/// version only for incompatible changes.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Document {
+    pub version: u32,
+    pub tasks: Vec<db::Task>,
+}
+
+pub fn export(tasks: &[db::Task]) -> Result<String> {
+    let document = Document {
+        version: VERSION,
+        tasks: tasks.to_vec(),
+    };
+    serde_json::to_string_pretty(&document).context(JsonSnafu {})
+}
+
+pub fn parse(content: &str) -> Result<Vec<db::Task>> {
+    let document: Document = serde_json::from_str(content).context(JsonSnafu {})?;
+    if document.version > VERSION {
+        return UnsupportedVersionSnafu {
+            version: document.version,
+            expected: VERSION,
+        }
+        .fail();
+    }
+    Ok(document.tasks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::stored_tasks;
+    use crate::format::{ImportMode, ImportReport, import_tasks};
+
+    fn sample_tasks() -> Vec<db::Task> {
+        let mut tasks = stored_tasks(&[
+            ("open task", db::TaskStatus::Open),
+            ("closed \"task\"\nwith two lines", db::TaskStatus::Closed),
+            ("deleted task", db::TaskStatus::Deleted),
+        ]);
+        tasks[0].priority = Some("A".to_owned());
+        tasks[0].project = Some("todo".to_owned());
+        tasks[0].tags = vec!["docs".to_owned()];
+        tasks
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let tasks = sample_tasks();
+        let content = export(&tasks).unwrap();
+        assert!(content.contains(r#""task": "closed \"task\"\nwith two lines""#));
+        assert_eq!(parse(&content).unwrap(), tasks);
+    }
+
+    #[test]
+    fn test_import_round_trip() {
+        let tasks = sample_tasks();
+        let conn = db::memory_connection();
+        let report = import_tasks(
+            &conn,
+            &parse(&export(&tasks).unwrap()).unwrap(),
+            ImportMode::Merge,
+        )
+        .unwrap();
+        assert_eq!(report.inserted, 3);
+        assert_eq!(db::list_all_tasks(&conn).unwrap(), tasks);
+
+        let report = import_tasks(&conn, &tasks, ImportMode::Merge).unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                duplicates: 3,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_import_replace() {
+        let conn = db::memory_connection();
+        db::insert_task(&conn, "local only").unwrap();
+        let mut tasks = sample_tasks();
+        import_tasks(&conn, &tasks, ImportMode::Merge).unwrap();
+
+        tasks[0].task = "changed".to_owned();
+        tasks.pop();
+        let report = import_tasks(&conn, &tasks, ImportMode::Replace).unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                updated: 1,
+                duplicates: 1,
+                removed: 2,
+                ..Default::default()
+            }
+        );
+        let texts: Vec<_> = db::list_all_tasks(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|task| task.task)
+            .collect();
+        assert_eq!(texts, vec!["changed", "closed \"task\"\nwith two lines"]);
+    }
+
+    #[test]
+    fn test_reject_newer_version() {
+        let content = r#"{"version": 999, "tasks": []}"#;
+        assert!(matches!(
+            parse(content),
+            Err(crate::format::FormatError::UnsupportedVersion { .. })
+        ));
+    }
+}
@@ -0,0 +1,246 @@
+//! Markdown checklists.
+//!
+//! Tasks are rendered as `- [ ]` and `- [x]` items under one `##` heading per
+//! group, deleted tasks are struck through. Following lines of a task are
+//! indented below its item. The parser reads every checklist item of a
+//! document and ignores everything else, so lists pasted from issues or wikis
+//! can be imported as they are.
+
+use std::collections::BTreeMap;
+
+use crate::db;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Group {
+    Status,
+    Project,
+    /// tasks with several tags are listed under each of them
+    Tag,
+}
+
+const INDENT: &str = "  ";
+const STRIKE: &str = "~~";
+
+fn render_item(task: &db::Task) -> String {
+    let checkbox = match task.status {
+        db::TaskStatus::Closed => "[x]",
+        _ => "[ ]",
+    };
+    let mut lines = task.task.lines();
+    let first = lines.next().unwrap_or_default();
+    let mut item = match task.status {
+        db::TaskStatus::Deleted => format!("- {} {}{}{}\n", checkbox, STRIKE, first, STRIKE),
+        _ => format!("- {} {}\n", checkbox, first),
+    };
+    for line in lines {
+        item += &format!("{}{}\n", INDENT, line);
+    }
+    item
+}
+
+fn render_groups<'a>(groups: impl IntoIterator<Item = (String, Vec<&'a db::Task>)>) -> String {
+    groups
+        .into_iter()
+        .filter(|(_, tasks)| !tasks.is_empty())
+        .map(|(heading, tasks)| {
+            let items: String = tasks.into_iter().map(render_item).collect();
+            format!("## {}\n\n{}", heading, items)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Renders a checklist, grouping by project or tag leaves out deleted tasks.
+pub fn export(tasks: &[db::Task], group: Group) -> String {
+    match group {
+        Group::Status => render_groups(
+            [
+                db::TaskStatus::Open,
+                db::TaskStatus::Closed,
+                db::TaskStatus::Deleted,
+            ]
+            .map(|status| {
+                let heading = match status {
+                    db::TaskStatus::Open => "Open",
+                    db::TaskStatus::Closed => "Closed",
+                    db::TaskStatus::Deleted => "Deleted",
+                };
+                let tasks = tasks.iter().filter(|task| task.status == status).collect();
+                (heading.to_owned(), tasks)
+            }),
+        ),
+        Group::Project | Group::Tag => {
+            let mut groups: BTreeMap<Option<&str>, Vec<&db::Task>> = BTreeMap::new();
+            let live = tasks
+                .iter()
+                .filter(|task| task.status != db::TaskStatus::Deleted);
+            for task in live {
+                let keys: Vec<Option<&str>> = match group {
+                    Group::Project => vec![task.project.as_deref()],
+                    _ if task.tags.is_empty() => vec![None],
+                    _ => task.tags.iter().map(|tag| Some(tag.as_str())).collect(),
+                };
+                for key in keys {
+                    groups.entry(key).or_default().push(task);
+                }
+            }
+            let fallback = match group {
+                Group::Project => "No project",
+                _ => "No tag",
+            };
+            // tasks without a project or tag come last
+            let none = groups.remove(&None).unwrap_or_default();
+            render_groups(
+                groups
+                    .into_iter()
+                    .map(|(key, tasks)| (key.unwrap_or_default().to_owned(), tasks))
+                    .chain(std::iter::once((fallback.to_owned(), none))),
+            )
+        }
+    }
+}
+
+/// Returns the status and text of a `- [ ] text` item.
+fn parse_item(line: &str) -> Option<(db::TaskStatus, &str)> {
+    let line = line.trim_start();
+    let line = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .or_else(|| line.strip_prefix("+ "))?;
+    let (status, text) = match line.get(..3)? {
+        "[ ]" => (db::TaskStatus::Open, &line[3..]),
+        "[x]" | "[X]" => (db::TaskStatus::Closed, &line[3..]),
+        _ => return None,
+    };
+    let text = text.trim();
+    match text
+        .strip_prefix(STRIKE)
+        .and_then(|text| text.strip_suffix(STRIKE))
+    {
+        Some(text) if !text.is_empty() => Some((db::TaskStatus::Deleted, text)),
+        _ => Some((status, text)),
+    }
+}
+
+pub fn parse(content: &str) -> Vec<db::Task> {
+    let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let mut tasks: Vec<db::Task> = Vec::new();
+    // whether indented lines continue the text of the last task
+    let mut in_item = false;
+    for line in content.lines() {
+        if let Some((status, text)) = parse_item(line) {
+            in_item = !text.is_empty();
+            if !in_item {
+                continue;
+            }
+            tasks.push(db::Task {
+                create_time: now.clone(),
+                task: text.to_owned(),
+                status,
+                ..Default::default()
+            });
+        } else if let (true, Some(text), Some(task)) =
+            (in_item, line.strip_prefix(INDENT), tasks.last_mut())
+        {
+            task.task += "\n";
+            task.task += text.trim();
+        } else {
+            in_item = false;
+        }
+    }
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::stored_tasks;
+
+    fn tasks() -> Vec<db::Task> {
+        let mut tasks = stored_tasks(&[
+            ("write docs", db::TaskStatus::Open),
+            ("review\nthe second line", db::TaskStatus::Closed),
+            ("old idea", db::TaskStatus::Deleted),
+            ("ship", db::TaskStatus::Open),
+        ]);
+        tasks[0].project = Some("todo".to_owned());
+        tasks[0].tags = vec!["docs".to_owned(), "easy".to_owned()];
+        tasks
+    }
+
+    #[test]
+    fn test_export() {
+        assert_eq!(
+            export(&tasks(), Group::Status),
+            "\
+## Open
+
+- [ ] write docs
+- [ ] ship
+
+## Closed
+
+- [x] review
+  the second line
+
+## Deleted
+
+- [ ] ~~old idea~~
+"
+        );
+        assert_eq!(
+            export(&tasks(), Group::Tag),
+            "\
+## docs
+
+- [ ] write docs
+
+## easy
+
+- [ ] write docs
+
+## No tag
+
+- [x] review
+  the second line
+- [ ] ship
+"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let parsed = parse(&export(&tasks(), Group::Status));
+        let summary: Vec<_> = parsed
+            .iter()
+            .map(|task| (task.status.clone(), task.task.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (db::TaskStatus::Open, "write docs"),
+                (db::TaskStatus::Open, "ship"),
+                (db::TaskStatus::Closed, "review\nthe second line"),
+                (db::TaskStatus::Deleted, "old idea"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_pasted_list() {
+        let content = "\
+# Sprint
+
+Some notes.
+
+* [X] done item
+- [ ] open item
+    - [ ] nested item
+- not a task
+  not a continuation
+- [ ]
+";
+        let texts: Vec<_> = parse(content).into_iter().map(|task| task.task).collect();
+        assert_eq!(texts, vec!["done item", "open item", "nested item"]);
+    }
+}
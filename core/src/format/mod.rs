@@ -0,0 +1,231 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use snafu::{ResultExt, prelude::Snafu};
+
+use crate::db::{self, SqlSnafu};
+
+pub mod csv;
+pub mod ics;
+pub mod json;
+pub mod markdown;
+pub mod taskwarrior;
+pub mod todotxt;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum FormatError {
+    #[snafu(display("invalid json"))]
+    Json { source: serde_json::Error },
+    #[snafu(display(
+        "unsupported interchange version {}, expected at most {}",
+        version,
+        expected
+    ))]
+    UnsupportedVersion { version: u32, expected: u32 },
+    #[snafu(display("line {}: {}", line, reason))]
+    Syntax { line: usize, reason: String },
+    #[snafu(display("invalid date '{}', expected YYYY-MM-DD", value))]
+    InvalidDate { value: String },
+}
+
+pub type Result<T> = std::result::Result<T, FormatError>;
+
+/// Inclusive range of days or times, open when a bound is `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DateRange {
+    since: Option<String>,
+    until: Option<String>,
+}
+
+impl DateRange {
+    /// Bounds are dates like `2025-01-31` or times like `2025-01-31 12:00:00`.
+    pub fn new(since: Option<&str>, until: Option<&str>) -> Result<Self> {
+        let check = |value: Option<&str>| -> Result<Option<String>> {
+            let Some(value) = value else {
+                return Ok(None);
+            };
+            let is_valid = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok()
+                || chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").is_ok();
+            match is_valid {
+                true => Ok(Some(value.to_owned())),
+                false => InvalidDateSnafu { value }.fail(),
+            }
+        };
+        Ok(Self {
+            since: check(since)?,
+            until: check(until)?,
+        })
+    }
+
+    fn contains_time(&self, time: &str) -> bool {
+        let since = self.since.as_deref().is_none_or(|since| time >= since);
+        // compare on the precision of the bound so that a date includes its whole day
+        let until = self
+            .until
+            .as_deref()
+            .is_none_or(|until| time.get(..until.len()).unwrap_or(time) <= until);
+        since && until
+    }
+
+    /// Whether the task was created or finished within the range.
+    pub fn contains(&self, task: &db::Task) -> bool {
+        self.contains_time(&task.create_time)
+            || task
+                .finished_time
+                .as_deref()
+                .is_some_and(|time| self.contains_time(time))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// add tasks which are not in the database yet
+    Merge,
+    /// make the database contain exactly the imported tasks
+    Replace,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ImportReport {
+    pub inserted: usize,
+    pub updated: usize,
+    pub duplicates: usize,
+    pub removed: usize,
+}
+
+/// Tasks are the same if they share the uuid, or for tasks coming from
+/// another database, if they were created at the same time with the same text.
+/// Formats without uuids or reliable times, like markdown, match by text.
+fn find_duplicate<'a>(
+    by_uuid: &'a BTreeMap<String, db::Task>,
+    task: &db::Task,
+) -> Option<&'a db::Task> {
+    if let Some(existing) = by_uuid.get(&task.uuid) {
+        return Some(existing);
+    }
+    by_uuid.values().find(|existing| {
+        existing.task == task.task
+            && (task.uuid.is_empty() || existing.create_time == task.create_time)
+    })
+}
+
+/// Writes `tasks` into the database in a single transaction.
+pub fn import_tasks(
+    conn: &db::Connection,
+    tasks: &[db::Task],
+    mode: ImportMode,
+) -> db::Result<ImportReport> {
+    db::ensure_table(conn)?;
+    let sql = "BEGIN";
+    let tx = conn
+        .raw()
+        .unchecked_transaction()
+        .context(SqlSnafu { sql })?;
+
+    let mut by_uuid: BTreeMap<String, db::Task> = db::list_all_tasks(conn)?
+        .into_iter()
+        .map(|task| (task.uuid.clone(), task))
+        .collect();
+    let mut report = ImportReport::default();
+    let mut imported = BTreeSet::new();
+    for task in tasks {
+        match find_duplicate(&by_uuid, task) {
+            None => {
+                let id = db::insert_full_task(conn, task)?;
+                report.inserted += 1;
+                // a task repeated later in the file is then a duplicate
+                if let Some(inserted) = db::get_task(conn, id)? {
+                    imported.insert(inserted.uuid.clone());
+                    by_uuid.insert(inserted.uuid.clone(), inserted);
+                }
+            }
+            Some(existing) => {
+                imported.insert(existing.uuid.clone());
+                let task = db::Task {
+                    id: existing.id,
+                    uuid: existing.uuid.clone(),
+                    ..task.clone()
+                };
+                if mode == ImportMode::Replace && task != *existing {
+                    db::update_full_task(conn, &task)?;
+                    report.updated += 1;
+                } else {
+                    report.duplicates += 1;
+                }
+            }
+        }
+    }
+    if mode == ImportMode::Replace {
+        for uuid in by_uuid.keys().filter(|uuid| !imported.contains(*uuid)) {
+            db::remove_task(conn, uuid)?;
+            report.removed += 1;
+        }
+    }
+
+    let sql = "COMMIT";
+    tx.commit().context(SqlSnafu { sql })?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stores `tasks` with their text and status and reads them back, the
+    /// fixture shared by the tests of every format.
+    pub(super) fn stored_tasks(tasks: &[(&str, db::TaskStatus)]) -> Vec<db::Task> {
+        let conn = db::memory_connection();
+        for (text, status) in tasks {
+            let id = db::insert_task_in(&conn, text, None).unwrap();
+            match status {
+                db::TaskStatus::Open => {}
+                db::TaskStatus::Closed => db::done_task(&conn, id).unwrap(),
+                db::TaskStatus::Deleted => db::delete_task(&conn, id).unwrap(),
+            }
+        }
+        db::list_all_tasks(&conn).unwrap()
+    }
+
+    #[test]
+    fn test_import_repeated_uuid() {
+        let conn = db::memory_connection();
+        let task = db::Task {
+            uuid: "0b6c7d8e-0000-4000-8000-000000000001".to_owned(),
+            create_time: "2025-01-01 00:00:00".to_owned(),
+            task: "first".to_owned(),
+            ..Default::default()
+        };
+        let renamed = db::Task {
+            task: "second".to_owned(),
+            ..task.clone()
+        };
+        let tasks = [task.clone(), renamed.clone()];
+
+        let report = import_tasks(&conn, &tasks, ImportMode::Merge).unwrap();
+        assert_eq!((report.inserted, report.duplicates), (1, 1));
+        assert_eq!(db::list_all_tasks(&conn).unwrap()[0].task, "first");
+
+        let conn = db::memory_connection();
+        let report = import_tasks(&conn, &tasks, ImportMode::Replace).unwrap();
+        assert_eq!((report.inserted, report.updated, report.removed), (1, 1, 0));
+        let imported = db::list_all_tasks(&conn).unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].task, "second");
+    }
+
+    #[test]
+    fn test_date_range() {
+        let conn = db::memory_connection();
+        db::insert_task(&conn, "task").unwrap();
+        let mut task = db::list_all_tasks(&conn).unwrap().remove(0);
+        task.create_time = "2025-01-10 12:00:00".to_owned();
+        let range = |since, until| DateRange::new(since, until).unwrap();
+        assert!(range(Some("2025-01-10"), Some("2025-01-10")).contains(&task));
+        assert!(!range(Some("2025-01-10 13:00:00"), None).contains(&task));
+        assert!(!range(None, Some("2025-01-09")).contains(&task));
+
+        task.finished_time = Some("2025-02-01 09:00:00".to_owned());
+        assert!(range(Some("2025-02-01"), None).contains(&task));
+        assert!(DateRange::new(Some("last week"), None).is_err());
+    }
+}
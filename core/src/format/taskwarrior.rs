@@ -0,0 +1,186 @@
+//! Output of Taskwarrior's `task export`.
+//!
+//! Both the JSON array of current versions and the one object per line of
+//! older versions are read. Fields without a counterpart in `Task` are
+//! counted in the `LossReport` instead of failing the import.
+
+use std::collections::BTreeMap;
+
+use snafu::ResultExt;
+
+use crate::db;
+use crate::format::ics::parse_time;
+use crate::format::{JsonSnafu, Result};
+
+/// Fields Taskwarrior computes or which only make sense inside Taskwarrior,
+/// dropping them loses nothing.
+const DERIVED_FIELDS: &[&str] = &["id", "urgency", "modified", "mask", "imask", "parent"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedTask {
+    /// uuid or description of the task
+    pub task: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LossReport {
+    pub skipped: Vec<SkippedTask>,
+    /// field name to the number of tasks whose value was dropped
+    pub dropped: BTreeMap<String, usize>,
+}
+
+type Object = serde_json::Map<String, serde_json::Value>;
+
+fn parse_objects(content: &str) -> Result<Vec<Object>> {
+    if content.trim_start().starts_with('[') {
+        return serde_json::from_str(content).context(JsonSnafu {});
+    }
+    content
+        .lines()
+        .map(|line| line.trim().trim_end_matches(','))
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_str(line).context(JsonSnafu {}))
+        .collect()
+}
+
+fn priority(value: &str) -> Option<String> {
+    match value {
+        "H" => Some("A".to_owned()),
+        "M" => Some("B".to_owned()),
+        "L" => Some("C".to_owned()),
+        _ => None,
+    }
+}
+
+/// Converts one exported task, `Err` holds the reason to skip it.
+fn convert(
+    mut object: Object,
+    dropped: &mut BTreeMap<String, usize>,
+) -> std::result::Result<db::Task, String> {
+    let mut take_str = |name: &str| match object.remove(name) {
+        Some(serde_json::Value::String(value)) => Ok(Some(value)),
+        Some(_) => Err(format!("'{}' is not a string", name)),
+        None => Ok(None),
+    };
+    let description = take_str("description")?.ok_or("no description")?;
+    let uuid = take_str("uuid")?.unwrap_or_default();
+    let status = match take_str("status")?.as_deref() {
+        Some("pending") | Some("waiting") | None => db::TaskStatus::Open,
+        Some("completed") => db::TaskStatus::Closed,
+        Some("deleted") => db::TaskStatus::Deleted,
+        Some("recurring") => return Err("recurring template".to_owned()),
+        Some(status) => return Err(format!("unknown status '{}'", status)),
+    };
+    let mut take_time = |name: &str| match take_str(name)? {
+        Some(value) => parse_time(&value)
+            .map(Some)
+            .ok_or_else(|| format!("invalid {} '{}'", name, value)),
+        None => Ok(None),
+    };
+    let create_time = take_time("entry")?
+        .unwrap_or_else(|| chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+    let finished_time = take_time("end")?;
+    let due_time = take_time("due")?;
+    let project = take_str("project")?;
+    let priority = match take_str("priority")? {
+        Some(value) => match priority(&value) {
+            Some(priority) => Some(priority),
+            None => {
+                *dropped.entry("priority".to_owned()).or_default() += 1;
+                None
+            }
+        },
+        None => None,
+    };
+    let tags = match object.remove("tags") {
+        Some(serde_json::Value::Array(tags)) => tags
+            .into_iter()
+            .filter_map(|tag| tag.as_str().map(str::to_owned))
+            .collect(),
+        Some(_) => return Err("'tags' is not an array".to_owned()),
+        None => Vec::new(),
+    };
+
+    for name in object.keys() {
+        if !DERIVED_FIELDS.contains(&name.as_str()) {
+            *dropped.entry(name.clone()).or_default() += 1;
+        }
+    }
+    Ok(db::Task {
+        uuid,
+        create_time,
+        finished_time,
+        task: description,
+        status,
+        priority,
+        project,
+        tags,
+        due_time,
+        ..Default::default()
+    })
+}
+
+pub fn parse(content: &str) -> Result<(Vec<db::Task>, LossReport)> {
+    let mut tasks = Vec::new();
+    let mut report = LossReport::default();
+    for object in parse_objects(content)? {
+        let name = ["uuid", "description"]
+            .iter()
+            .find_map(|name| object.get(*name)?.as_str())
+            .unwrap_or_default()
+            .to_owned();
+        match convert(object, &mut report.dropped) {
+            Ok(task) => tasks.push(task),
+            Err(reason) => report.skipped.push(SkippedTask { task: name, reason }),
+        }
+    }
+    Ok((tasks, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"[
+{"id":1,"description":"fix the roof","entry":"20250101T100000Z","modified":"20250102T100000Z","project":"home","priority":"H","status":"pending","tags":["diy","urgent"],"due":"20250201T000000Z","uuid":"2b6d9e1c-0000-4000-8000-000000000001","urgency":9.1,"annotations":[{"entry":"20250101T100000Z","description":"call roofer"}]},
+{"id":0,"description":"pay taxes","end":"20250301T080000Z","entry":"20250110T080000Z","status":"completed","uuid":"2b6d9e1c-0000-4000-8000-000000000002","priority":"X","urgency":0},
+{"id":0,"description":"gym","entry":"20250110T080000Z","status":"recurring","recur":"weekly","uuid":"2b6d9e1c-0000-4000-8000-000000000003"},
+{"id":2,"entry":"20250110T080000Z","status":"pending","uuid":"2b6d9e1c-0000-4000-8000-000000000004"}
+]"#;
+
+    #[test]
+    fn test_parse() {
+        let (tasks, report) = parse(EXPORT).unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].task, "fix the roof");
+        assert_eq!(tasks[0].uuid, "2b6d9e1c-0000-4000-8000-000000000001");
+        assert_eq!(tasks[0].status, db::TaskStatus::Open);
+        assert_eq!(tasks[0].priority.as_deref(), Some("A"));
+        assert_eq!(tasks[0].project.as_deref(), Some("home"));
+        assert_eq!(tasks[0].tags, vec!["diy", "urgent"]);
+        assert!(tasks[0].due_time.is_some());
+        assert_eq!(tasks[1].status, db::TaskStatus::Closed);
+        assert!(tasks[1].finished_time.is_some());
+        assert_eq!(tasks[1].priority, None);
+
+        let skipped: Vec<_> = report
+            .skipped
+            .iter()
+            .map(|skipped| skipped.reason.as_str())
+            .collect();
+        assert_eq!(skipped, vec!["recurring template", "no description"]);
+        let dropped: Vec<_> = report.dropped.into_iter().collect();
+        assert_eq!(
+            dropped,
+            vec![("annotations".to_owned(), 1), ("priority".to_owned(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_parse_lines() {
+        let content = EXPORT.trim_start_matches('[').trim_end_matches(']').trim();
+        let (tasks, _) = parse(content).unwrap();
+        assert_eq!(tasks.len(), 2);
+    }
+}
@@ -0,0 +1,205 @@
+//! [todo.txt](https://github.com/todotxt/todo.txt) lines.
+//!
+//! Projects and contexts stay inline in the task text like todo.txt expects,
+//! the first `+project` and every `@context` are also copied into
+//! `Task::project` and `Task::tags`. The common `due:` extension holds the
+//! due date, two more keep round-trips lossless: `uuid:` identifies the task
+//! and `pri:` holds the priority of completed tasks. Times of day and deleted
+//! tasks are not exported, line breaks in the task text become spaces.
+
+use crate::db;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const UUID_KEY: &str = "uuid:";
+const PRIORITY_KEY: &str = "pri:";
+const DUE_KEY: &str = "due:";
+
+fn date(time: &str) -> &str {
+    time.get(..10).unwrap_or(time)
+}
+
+fn is_date(token: &str) -> bool {
+    chrono::NaiveDate::parse_from_str(token, DATE_FORMAT).is_ok()
+}
+
+fn parse_priority(token: &str) -> Option<String> {
+    let priority = token.strip_prefix('(')?.strip_suffix(')')?;
+    is_priority(priority).then(|| priority.to_owned())
+}
+
+fn is_priority(priority: &str) -> bool {
+    priority.len() == 1 && priority.chars().all(|c| c.is_ascii_uppercase())
+}
+
+fn has_token(text: &str, token: &str) -> bool {
+    text.split_whitespace().any(|word| word == token)
+}
+
+pub fn serialize_task(task: &db::Task) -> String {
+    let mut line = Vec::new();
+    let is_closed = task.status == db::TaskStatus::Closed;
+    if is_closed {
+        line.push("x".to_owned());
+        if let Some(finished_time) = &task.finished_time {
+            line.push(date(finished_time).to_owned());
+        }
+    } else if let Some(priority) = &task.priority {
+        line.push(format!("({})", priority));
+    }
+    line.push(date(&task.create_time).to_owned());
+
+    let text = task.task.split_whitespace().collect::<Vec<_>>().join(" ");
+    line.push(text.clone());
+    let project = task.project.iter().map(|project| format!("+{}", project));
+    let tags = task.tags.iter().map(|tag| format!("@{}", tag));
+    line.extend(project.chain(tags).filter(|token| !has_token(&text, token)));
+    if let Some(due_time) = &task.due_time {
+        line.push(format!("{}{}", DUE_KEY, date(due_time)));
+    }
+    if let (true, Some(priority)) = (is_closed, &task.priority) {
+        line.push(format!("{}{}", PRIORITY_KEY, priority));
+    }
+    if !task.uuid.is_empty() {
+        line.push(format!("{}{}", UUID_KEY, task.uuid));
+    }
+    line.join(" ")
+}
+
+/// Returns `None` for blank lines.
+pub fn parse_task(line: &str) -> Option<db::Task> {
+    let mut tokens = line.split_whitespace().peekable();
+    tokens.peek()?;
+
+    let mut status = db::TaskStatus::Open;
+    let mut priority = None;
+    let mut finished_time = None;
+    if tokens.next_if_eq(&"x").is_some() {
+        status = db::TaskStatus::Closed;
+        finished_time = tokens.next_if(|token| is_date(token));
+    } else if let Some(token) = tokens.next_if(|token| parse_priority(token).is_some()) {
+        priority = parse_priority(token);
+    }
+    let create_time = match tokens.next_if(|token| is_date(token)) {
+        Some(create_time) => create_time.to_owned(),
+        // a single date on a completed task is the completion date
+        None => finished_time
+            .map(str::to_owned)
+            .unwrap_or_else(|| chrono::Local::now().format(DATE_FORMAT).to_string()),
+    };
+
+    let mut uuid = String::new();
+    let mut due_time = None;
+    let mut project = None;
+    let mut tags: Vec<String> = Vec::new();
+    let mut text = Vec::new();
+    for token in tokens {
+        if let Some(value) = token.strip_prefix(UUID_KEY) {
+            uuid = value.to_owned();
+            continue;
+        }
+        if let Some(value) = token.strip_prefix(DUE_KEY)
+            && is_date(value)
+        {
+            due_time = Some(format!("{} 00:00:00", value));
+            continue;
+        }
+        if let Some(value) = token.strip_prefix(PRIORITY_KEY)
+            && status == db::TaskStatus::Closed
+            && is_priority(value)
+        {
+            priority = Some(value.to_owned());
+            continue;
+        }
+        match (token.strip_prefix('+'), token.strip_prefix('@')) {
+            (Some(name), _) if !name.is_empty() && project.is_none() => {
+                project = Some(name.to_owned())
+            }
+            (_, Some(name)) if !name.is_empty() && !tags.iter().any(|tag| tag == name) => {
+                tags.push(name.to_owned())
+            }
+            _ => {}
+        }
+        text.push(token);
+    }
+
+    Some(db::Task {
+        uuid,
+        create_time: format!("{} 00:00:00", create_time),
+        finished_time: finished_time.map(|time| format!("{} 00:00:00", time)),
+        task: text.join(" "),
+        status,
+        priority,
+        project,
+        tags,
+        due_time,
+        ..Default::default()
+    })
+}
+
+/// Serializes the open and closed tasks, one per line.
+pub fn export(tasks: &[db::Task]) -> String {
+    tasks
+        .iter()
+        .filter(|task| task.status != db::TaskStatus::Deleted)
+        .map(|task| serialize_task(task) + "\n")
+        .collect()
+}
+
+pub fn parse(content: &str) -> Vec<db::Task> {
+    content.lines().filter_map(parse_task).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tests::stored_tasks;
+
+    #[test]
+    fn test_parse_task() {
+        let task = parse_task("x 2025-01-03 2025-01-01 review +web PR @work @home pri:B").unwrap();
+        assert_eq!(task.status, db::TaskStatus::Closed);
+        assert_eq!(task.create_time, "2025-01-01 00:00:00");
+        assert_eq!(task.finished_time.as_deref(), Some("2025-01-03 00:00:00"));
+        assert_eq!(task.task, "review +web PR @work @home");
+        assert_eq!(task.priority.as_deref(), Some("B"));
+        assert_eq!(task.project.as_deref(), Some("web"));
+        assert_eq!(task.tags, vec!["work", "home"]);
+
+        let task = parse_task("(A) call mom").unwrap();
+        assert_eq!(task.status, db::TaskStatus::Open);
+        assert_eq!(task.priority.as_deref(), Some("A"));
+        assert_eq!(task.task, "call mom");
+        assert!(parse_task("  ").is_none());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let content = "\
+(A) 2025-01-01 write docs +todo @desk due:2025-01-10 uuid:1
+x 2025-01-03 2025-01-02 ship release +todo pri:C uuid:2
+2025-01-02 (b) is not a priority uuid:3
+";
+        assert_eq!(export(&parse(content)), content);
+    }
+
+    #[test]
+    fn test_serialize_metadata() {
+        let mut task = stored_tasks(&[("plan\nsprint", db::TaskStatus::Open)]).remove(0);
+        task.priority = Some("B".to_owned());
+        task.project = Some("todo".to_owned());
+        task.tags = vec!["work".to_owned()];
+        let line = serialize_task(&task);
+        assert_eq!(
+            line,
+            format!(
+                "(B) {} plan sprint +todo @work uuid:{}",
+                date(&task.create_time),
+                task.uuid
+            )
+        );
+        let parsed = parse_task(&line).unwrap();
+        assert_eq!(parsed.project, task.project);
+        assert_eq!(parsed.tags, task.tags);
+        assert_eq!(parsed.priority, task.priority);
+    }
+}
@@ -1,2 +1,14 @@
+pub mod backup;
 pub mod db;
+pub mod encryption;
+pub mod format;
+pub mod link;
+pub mod remote;
+pub mod repo;
+pub mod report;
 pub mod root_path;
+pub mod scan;
+pub mod sync;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+pub mod watch;
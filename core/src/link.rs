@@ -0,0 +1,248 @@
+use snafu::ResultExt;
+
+use crate::db::{self, SqlSnafu};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    Branch,
+    Commit,
+    Url,
+}
+
+impl LinkKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkKind::Branch => "branch",
+            LinkKind::Commit => "commit",
+            LinkKind::Url => "url",
+        }
+    }
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "branch" => Some(LinkKind::Branch),
+            "commit" => Some(LinkKind::Commit),
+            "url" => Some(LinkKind::Url),
+            _ => None,
+        }
+    }
+    /// Guesses the kind of a reference: urls have a scheme, commits are
+    /// abbreviated or full hex SHAs, anything else is a branch.
+    pub fn infer(target: &str) -> Self {
+        if target.contains("://") {
+            LinkKind::Url
+        } else if (7..=40).contains(&target.len()) && target.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            LinkKind::Commit
+        } else {
+            LinkKind::Branch
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Link {
+    pub task_uuid: String,
+    pub kind: LinkKind,
+    pub target: String,
+    pub create_time: String,
+}
+
+/// Creates `todo_link`, which references tasks by uuid rather than by id so
+/// that links survive imports. Links are local and not synced.
+pub(crate) fn create_link_table(conn: &db::Connection) -> db::Result<()> {
+    let sql = r##"
+    CREATE TABLE IF NOT EXISTS todo_link
+    (
+        task_uuid TEXT NOT NULL,
+        kind TEXT NOT NULL CHECK (kind IN ('branch', 'commit', 'url')),
+        target TEXT NOT NULL,
+        create_time TIMESTAMP NOT NULL DEFAULT (DATETIME('now', 'localtime')),
+        PRIMARY KEY (task_uuid, kind, target)
+    )
+    "##;
+    conn.execute(sql, []).context(SqlSnafu { sql })?;
+    Ok(())
+}
+
+/// Links `target` to the task, linking the same target twice is a no-op.
+/// Returns whether a new link was added.
+pub fn add_link(
+    conn: &db::Connection,
+    task_uuid: &str,
+    kind: LinkKind,
+    target: &str,
+) -> db::Result<bool> {
+    let sql = "INSERT OR IGNORE INTO todo_link (task_uuid, kind, target) VALUES (?1, ?2, ?3)";
+    let count = conn
+        .execute(sql, rusqlite::params![task_uuid, kind.as_str(), target])
+        .context(SqlSnafu { sql })?;
+    Ok(count > 0)
+}
+
+pub fn remove_link(conn: &db::Connection, task_uuid: &str, target: &str) -> db::Result<bool> {
+    let sql = "DELETE FROM todo_link WHERE task_uuid = ?1 AND target = ?2";
+    let count = conn
+        .execute(sql, [task_uuid, target])
+        .context(SqlSnafu { sql })?;
+    Ok(count > 0)
+}
+
+pub fn list_links(conn: &db::Connection, task_uuid: &str) -> db::Result<Vec<Link>> {
+    let sql = r##"
+    SELECT task_uuid, kind, target, create_time FROM todo_link
+    WHERE task_uuid = ?1 ORDER BY rowid
+    "##;
+    let rows = (|| -> rusqlite::Result<Vec<(String, String, String, String)>> {
+        let mut stmt = conn.raw().prepare(sql)?;
+        stmt.query_map([task_uuid], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect()
+    })()
+    .context(SqlSnafu { sql })?;
+    rows.into_iter()
+        .map(|(task_uuid, kind, target, create_time)| {
+            Ok(Link {
+                task_uuid,
+                kind: LinkKind::parse(&kind).ok_or(db::DBError::InvalidDatabase {})?,
+                target,
+                create_time,
+            })
+        })
+        .collect()
+}
+
+/// Name of the branch `todo start` creates, e.g. `todo-12-fix-the-parser`.
+pub fn branch_name(id: i64, task: &str) -> String {
+    const MAX_SLUG: usize = 40;
+    let first_line = task.lines().next().unwrap_or_default();
+    let mut slug = String::new();
+    for word in first_line
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        if !slug.is_empty() && slug.len() + word.len() + 1 > MAX_SLUG {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word.to_ascii_lowercase());
+    }
+    slug.truncate(MAX_SLUG);
+    match slug.is_empty() {
+        true => format!("todo-{}", id),
+        false => format!("todo-{}-{}", id, slug),
+    }
+}
+
+/// A `todo#<id>` mention in a commit message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reference {
+    pub id: i64,
+    /// the mention follows a closing keyword, as in `closes todo#12`
+    pub closes: bool,
+}
+
+const REFERENCE_PREFIX: &str = "todo#";
+const CLOSING_KEYWORDS: &[&str] = &[
+    "close", "closes", "closed", "fix", "fixes", "fixed", "resolve", "resolves", "resolved",
+];
+
+pub fn parse_references(message: &str) -> Vec<Reference> {
+    let mut references: Vec<Reference> = Vec::new();
+    // whether the following references are preceded by a closing keyword
+    let mut closing = false;
+    // whether the previous word was a closed reference, continued by `and`
+    let mut closed_previous = false;
+    for word in message.split_whitespace() {
+        let word = word.to_ascii_lowercase();
+        let bare = word.trim_end_matches([':', ',', '.', ';']);
+        if CLOSING_KEYWORDS.contains(&bare) || (closed_previous && bare == "and") {
+            closing = true;
+            closed_previous = false;
+            continue;
+        }
+        let id = word
+            .strip_prefix(REFERENCE_PREFIX)
+            .and_then(|rest| rest.split(|c: char| !c.is_ascii_digit()).next())
+            .and_then(|digits| digits.parse::<i64>().ok());
+        let Some(id) = id else {
+            closing = false;
+            closed_previous = false;
+            continue;
+        };
+        match references.iter_mut().find(|reference| reference.id == id) {
+            Some(reference) => reference.closes |= closing,
+            None => references.push(Reference {
+                id,
+                closes: closing,
+            }),
+        }
+        // `closes todo#1, todo#2` closes both
+        closed_previous = closing;
+        closing &= word.ends_with(',');
+    }
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_references() {
+        let message = "Fix the parser for todo#3\n\nCloses todo#12, todo#13. See todo#x and todo#3\n\nfixes: todo#4 and todo#5";
+        assert_eq!(
+            parse_references(message),
+            vec![
+                Reference {
+                    id: 3,
+                    closes: false
+                },
+                Reference {
+                    id: 12,
+                    closes: true
+                },
+                Reference {
+                    id: 13,
+                    closes: true
+                },
+                Reference {
+                    id: 4,
+                    closes: true
+                },
+                Reference {
+                    id: 5,
+                    closes: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_links() {
+        let conn = db::memory_connection();
+        db::insert_task(&conn, "Fix the parser: handle `\"quotes\"` & more").unwrap();
+        let task = db::get_task(&conn, 1).unwrap().unwrap();
+        assert_eq!(
+            branch_name(task.id, &task.task),
+            "todo-1-fix-the-parser-handle-quotes-more"
+        );
+
+        let branch = branch_name(task.id, &task.task);
+        assert!(add_link(&conn, &task.uuid, LinkKind::Branch, &branch).unwrap());
+        assert!(!add_link(&conn, &task.uuid, LinkKind::Branch, &branch).unwrap());
+        let target = "https://example.com/issues/1";
+        add_link(&conn, &task.uuid, LinkKind::infer(target), target).unwrap();
+        let kinds: Vec<_> = list_links(&conn, &task.uuid)
+            .unwrap()
+            .into_iter()
+            .map(|link| link.kind)
+            .collect();
+        assert_eq!(kinds, vec![LinkKind::Branch, LinkKind::Url]);
+        assert!(remove_link(&conn, &task.uuid, &branch).unwrap());
+        assert_eq!(LinkKind::infer("3f2a9c1"), LinkKind::Commit);
+    }
+}
@@ -0,0 +1,63 @@
+use snafu::{ResultExt, prelude::Snafu};
+
+use crate::db;
+use crate::sync;
+
+#[derive(Debug, Snafu)]
+pub enum RemoteError {
+    #[snafu(display("database error when syncing with '{}'", url))]
+    Database { source: db::DBError, url: String },
+    #[snafu(display("failed to request '{}'", url))]
+    Http { source: ureq::Error, url: String },
+}
+
+type Result<T> = std::result::Result<T, RemoteError>;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PullResponse {
+    pub cursor: i64,
+    pub changes: sync::ChangeSet,
+}
+
+fn pull_key(url: &str) -> String {
+    format!("pull:{}", url)
+}
+
+fn push_key(url: &str) -> String {
+    format!("push:{}", url)
+}
+
+/// Pulls the changes the server received since the last pull, then pushes
+/// the local changes made since the last push. `url` is the base url of a
+/// `todo_server`, e.g. `http://127.0.0.1:8765`.
+pub fn sync_with_server(conn: &db::Connection, url: &str) -> Result<sync::SyncReport> {
+    let url = url.trim_end_matches('/');
+    db::ensure_table(conn).context(DatabaseSnafu { url })?;
+
+    let cursor = sync::get_sync_state(conn, &pull_key(url))
+        .context(DatabaseSnafu { url })?
+        .and_then(|cursor| cursor.parse::<i64>().ok())
+        .unwrap_or(0);
+    let pull_url = format!("{}/pull?since={}", url, cursor);
+    let pulled: PullResponse = ureq::get(&pull_url)
+        .call()
+        .and_then(|mut response| response.body_mut().read_json())
+        .context(HttpSnafu { url: &pull_url })?;
+    let local = sync::apply_changes(conn, &pulled.changes).context(DatabaseSnafu { url })?;
+    sync::set_sync_state(conn, &pull_key(url), &pulled.cursor.to_string())
+        .context(DatabaseSnafu { url })?;
+
+    let last_push = sync::get_sync_state(conn, &push_key(url)).context(DatabaseSnafu { url })?;
+    // the local clock, as pulled rows may carry the later times of a peer
+    let push_time = sync::current_time(conn).context(DatabaseSnafu { url })?;
+    let changes =
+        sync::collect_changes(conn, last_push.as_deref()).context(DatabaseSnafu { url })?;
+    let push_url = format!("{}/push", url);
+    let remote: sync::MergeReport = ureq::post(&push_url)
+        .send_json(&changes)
+        .and_then(|mut response| response.body_mut().read_json())
+        .context(HttpSnafu { url: &push_url })?;
+    sync::set_sync_state(conn, &push_key(url), &push_time).context(DatabaseSnafu { url })?;
+
+    Ok(sync::SyncReport { local, remote })
+}
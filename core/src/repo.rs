@@ -0,0 +1,139 @@
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repository {
+    /// the working tree, i.e. the directory containing `.git`
+    pub root: PathBuf,
+    /// url of the `origin` remote, or of the first remote
+    pub remote: Option<String>,
+}
+
+impl Repository {
+    /// Identifies the repository in the `repo` column of tasks. The remote is
+    /// preferred over the root so that clones on other machines share tasks.
+    pub fn key(&self) -> String {
+        match &self.remote {
+            Some(remote) => normalize_remote(remote),
+            None => self.root.to_string_lossy().to_string(),
+        }
+    }
+}
+
+/// Turns the different spellings of a remote into `host/path`, e.g. both
+/// `git@github.com:me/todo.git` and `https://github.com/me/todo` become
+/// `github.com/me/todo`.
+fn normalize_remote(url: &str) -> String {
+    let (url, is_scp_like) = match url.split_once("://") {
+        Some((_, rest)) => (rest, false),
+        None => (url, true),
+    };
+    let url = url.split_once('@').map_or(url, |(_, host)| host);
+    let url = match (is_scp_like, url.split_once(':')) {
+        (true, Some((host, path))) => format!("{}/{}", host, path),
+        _ => url.to_owned(),
+    };
+    url.trim_end_matches('/')
+        .trim_end_matches(".git")
+        .trim_end_matches('/')
+        .to_owned()
+}
+
+/// Returns the git directory of the working tree at `root`, following the
+/// `gitdir:` file of worktrees and submodules.
+fn git_dir(root: &Path) -> Option<PathBuf> {
+    let dot_git = root.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let content = std::fs::read_to_string(&dot_git).ok()?;
+    let dir = root.join(content.trim().strip_prefix("gitdir:")?.trim());
+    // worktrees keep the config in the main git directory
+    match std::fs::read_to_string(dir.join("commondir")) {
+        Ok(common) => Some(dir.join(common.trim())),
+        Err(_) => Some(dir),
+    }
+}
+
+/// Reads the url of `origin`, or of the first remote, from a git config.
+fn read_remote(config: &str) -> Option<String> {
+    let mut remotes = Vec::new();
+    let mut section = None;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            section = line
+                .strip_prefix("[remote \"")
+                .and_then(|line| line.strip_suffix("\"]"))
+                .map(str::to_owned);
+            continue;
+        }
+        let (Some(name), Some((key, value))) = (&section, line.split_once('=')) else {
+            continue;
+        };
+        if key.trim() == "url" {
+            remotes.push((name.clone(), value.trim().to_owned()));
+        }
+    }
+    remotes
+        .iter()
+        .find(|(name, _)| name == "origin")
+        .or(remotes.first())
+        .map(|(_, url)| url.clone())
+}
+
+/// Finds the git repository containing `dir` by walking up to `.git`.
+pub fn detect_repository(dir: &Path) -> Option<Repository> {
+    let dir = std::fs::canonicalize(dir).ok()?;
+    let root = dir.ancestors().find(|dir| dir.join(".git").exists())?;
+    let remote = git_dir(root)
+        .and_then(|git_dir| std::fs::read_to_string(git_dir.join("config")).ok())
+        .and_then(|config| read_remote(&config));
+    Some(Repository {
+        root: root.to_owned(),
+        remote,
+    })
+}
+
+/// Repository of the working directory of the process.
+pub fn current_repository() -> Option<Repository> {
+    detect_repository(&std::env::current_dir().ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_remote() {
+        for url in [
+            "git@github.com:me/todo.git",
+            "https://github.com/me/todo",
+            "https://user@github.com/me/todo.git/",
+            "ssh://git@github.com/me/todo.git",
+        ] {
+            assert_eq!(normalize_remote(url), "github.com/me/todo", "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_detect_repository() {
+        let root = std::env::temp_dir().join(format!("todo-repo-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("src/nested")).unwrap();
+        std::fs::write(
+            root.join(".git/config"),
+            "[core]\n\tbare = false\n[remote \"upstream\"]\n\turl = https://example.com/up.git\n\
+             [remote \"origin\"]\n\turl = git@example.com:me/todo.git\n",
+        )
+        .unwrap();
+
+        let repo = detect_repository(&root.join("src/nested")).unwrap();
+        assert_eq!(repo.root, std::fs::canonicalize(&root).unwrap());
+        assert_eq!(repo.key(), "example.com/me/todo");
+
+        std::fs::write(root.join(".git/config"), "[core]\n").unwrap();
+        let repo = detect_repository(&root).unwrap();
+        assert_eq!(repo.key(), repo.root.to_string_lossy());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,242 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, TimeDelta};
+
+use crate::db;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Tasks created and completed during a day or a week, and the tasks open at
+/// its end.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Period {
+    /// first day, `YYYY-MM-DD`
+    pub start: String,
+    pub created: usize,
+    pub completed: usize,
+    pub open: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Report {
+    pub open: usize,
+    /// the last days, oldest first, ending today
+    pub days: Vec<Period>,
+    /// the last weeks starting on Monday, oldest first, ending with this week
+    pub weeks: Vec<Period>,
+    /// mean seconds from creation to completion of the closed tasks
+    pub average_close_seconds: Option<i64>,
+    /// the open tasks created first
+    pub oldest_open: Vec<db::OpenTask>,
+    /// days in a row with a completed task, up to today or yesterday
+    pub current_streak: usize,
+    pub longest_streak: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ReportOptions {
+    pub days: usize,
+    pub weeks: usize,
+    pub oldest: usize,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        Self {
+            days: 14,
+            weeks: 8,
+            oldest: 5,
+        }
+    }
+}
+
+/// Reports on the tasks of the repository with the key `repo`, or of every
+/// repository when `repo` is `None`.
+pub fn build_report(
+    conn: &db::Connection,
+    repo: Option<&str>,
+    options: &ReportOptions,
+) -> db::Result<Report> {
+    let tasks = db::list_all_tasks_in(conn, repo)?;
+    Ok(compute(&tasks, chrono::Local::now().date_naive(), options))
+}
+
+/// Creation and completion time of a task, `None` while it is open.
+struct Span {
+    created: NaiveDateTime,
+    finished: Option<NaiveDateTime>,
+}
+
+impl Span {
+    fn open_before(&self, end: NaiveDate) -> bool {
+        self.created.date() < end && self.finished.is_none_or(|finished| finished.date() >= end)
+    }
+}
+
+fn period(spans: &[Span], start: NaiveDate, end: NaiveDate) -> Period {
+    let within = |time: &NaiveDateTime| (start..end).contains(&time.date());
+    Period {
+        start: start.to_string(),
+        created: spans.iter().filter(|span| within(&span.created)).count(),
+        completed: spans
+            .iter()
+            .filter(|span| span.finished.as_ref().is_some_and(within))
+            .count(),
+        open: spans.iter().filter(|span| span.open_before(end)).count(),
+    }
+}
+
+/// Computes the report as of `today`. Deleted tasks carry no deletion time,
+/// so they are left out.
+pub fn compute(tasks: &[db::Task], today: NaiveDate, options: &ReportOptions) -> Report {
+    let parse = |time: &str| NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok();
+    let spans: Vec<Span> = tasks
+        .iter()
+        .filter_map(|task| {
+            let finished = match task.status {
+                db::TaskStatus::Open => None,
+                db::TaskStatus::Closed => Some(parse(task.finished_time.as_deref()?)?),
+                db::TaskStatus::Deleted => return None,
+            };
+            Some(Span {
+                created: parse(&task.create_time)?,
+                finished,
+            })
+        })
+        .collect();
+
+    let days = (0..options.days as i64)
+        .rev()
+        .map(|ago| today - TimeDelta::days(ago))
+        .map(|day| period(&spans, day, day + TimeDelta::days(1)))
+        .collect();
+    let monday = today - TimeDelta::days(today.weekday().num_days_from_monday() as i64);
+    let weeks = (0..options.weeks as i64)
+        .rev()
+        .map(|ago| monday - TimeDelta::weeks(ago))
+        .map(|start| period(&spans, start, start + TimeDelta::weeks(1)))
+        .collect();
+
+    let durations: Vec<i64> = spans
+        .iter()
+        .filter_map(|span| Some((span.finished? - span.created).num_seconds()))
+        .collect();
+    let average_close_seconds = match durations.len() {
+        0 => None,
+        count => Some(durations.iter().sum::<i64>() / count as i64),
+    };
+
+    let mut open: Vec<&db::Task> = tasks
+        .iter()
+        .filter(|task| task.status == db::TaskStatus::Open)
+        .collect();
+    // `YYYY-MM-DD HH:MM:SS` sorts chronologically
+    open.sort_by(|a, b| a.create_time.cmp(&b.create_time).then(a.id.cmp(&b.id)));
+    let oldest_open = open
+        .iter()
+        .take(options.oldest)
+        .map(|task| db::OpenTask {
+            id: task.id,
+            uuid: task.uuid.clone(),
+            create_time: task.create_time.clone(),
+            task: task.task.clone(),
+        })
+        .collect();
+
+    let completed: std::collections::BTreeSet<NaiveDate> = spans
+        .iter()
+        .filter_map(|span| Some(span.finished?.date()))
+        .collect();
+    let mut longest_streak = 0;
+    let mut streak = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for day in &completed {
+        streak = match previous {
+            Some(previous) if *day - previous == TimeDelta::days(1) => streak + 1,
+            _ => 1,
+        };
+        longest_streak = longest_streak.max(streak);
+        previous = Some(*day);
+    }
+    // today still counts for the streak until it ends
+    let mut day = match completed.contains(&today) {
+        true => today,
+        false => today - TimeDelta::days(1),
+    };
+    let mut current_streak = 0;
+    while completed.contains(&day) {
+        current_streak += 1;
+        day -= TimeDelta::days(1);
+    }
+
+    Report {
+        open: open.len(),
+        days,
+        weeks,
+        average_close_seconds,
+        oldest_open,
+        current_streak,
+        longest_streak,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: i64, created: &str, finished: Option<&str>, status: db::TaskStatus) -> db::Task {
+        db::Task {
+            id,
+            uuid: format!("uuid-{}", id),
+            create_time: format!("{} 10:00:00", created),
+            finished_time: finished.map(|day| format!("{} 12:00:00", day)),
+            task: format!("task {}", id),
+            status,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compute() {
+        use db::TaskStatus::{Closed, Deleted, Open};
+        let tasks = vec![
+            task(1, "2025-03-01", Some("2025-03-08"), Closed),
+            task(2, "2025-03-08", Some("2025-03-09"), Closed),
+            task(3, "2025-03-09", Some("2025-03-10"), Closed),
+            task(4, "2025-03-02", None, Open),
+            task(5, "2025-03-10", None, Open),
+            task(6, "2025-03-10", None, Deleted),
+        ];
+        let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let options = ReportOptions {
+            days: 3,
+            weeks: 2,
+            oldest: 1,
+        };
+        let report = compute(&tasks, today, &options);
+        assert_eq!(report.open, 2);
+        let days: Vec<(&str, usize, usize, usize)> = report
+            .days
+            .iter()
+            .map(|day| (day.start.as_str(), day.created, day.completed, day.open))
+            .collect();
+        assert_eq!(
+            days,
+            vec![
+                ("2025-03-08", 1, 1, 2),
+                ("2025-03-09", 1, 1, 2),
+                ("2025-03-10", 1, 1, 2),
+            ]
+        );
+        // 2025-03-10 is a Monday
+        assert_eq!(report.weeks[0].start, "2025-03-03");
+        assert_eq!((report.weeks[0].created, report.weeks[0].completed), (2, 2));
+        assert_eq!(report.weeks[0].open, 2);
+        // 7 days 2 hours, 1 day 2 hours and 1 day 2 hours
+        assert_eq!(report.average_close_seconds, Some((9 * 24 + 6) * 3600 / 3));
+        assert_eq!(report.oldest_open.len(), 1);
+        assert_eq!(report.oldest_open[0].id, 4);
+        assert_eq!((report.current_streak, report.longest_streak), (3, 3));
+
+        let report = compute(&tasks, today + TimeDelta::days(2), &options);
+        assert_eq!((report.current_streak, report.longest_streak), (0, 3));
+    }
+}
@@ -0,0 +1,253 @@
+use snafu::{ResultExt, prelude::Snafu};
+
+use crate::db;
+
+#[derive(Debug, Snafu)]
+pub enum ScanError {
+    #[snafu(display("database error when {}", cases))]
+    Database {
+        source: db::DBError,
+        cases: &'static str,
+    },
+    #[snafu(display("failed to access '{}'", path))]
+    Io {
+        source: std::io::Error,
+        path: String,
+    },
+    #[snafu(display("failed to walk '{}'", path))]
+    Walk { source: ignore::Error, path: String },
+}
+
+type Result<T> = std::result::Result<T, ScanError>;
+
+/// Words which turn a comment into a task.
+pub const MARKERS: &[&str] = &["TODO", "FIXME", "HACK"];
+const COMMENT_STARTS: &[&str] = &["//", "/*", "#", "--", ";", "<!--"];
+const COMMENT_ENDS: &[&str] = &["*/", "-->"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    pub path: String,
+    pub line: usize,
+    /// the marker followed by the comment text, e.g. `TODO: handle errors`
+    pub text: String,
+}
+
+impl Comment {
+    pub fn source(&self) -> String {
+        format!("{}:{}", self.path, self.line)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanReport {
+    pub added: Vec<Comment>,
+    pub moved: usize,
+    pub closed: Vec<db::Task>,
+}
+
+/// Returns `MARKER: text` when `comment` starts with a marker.
+fn parse_marker(comment: &str) -> Option<String> {
+    let comment = comment.trim_start_matches(['/', '*', '!', '#', '-', ';', ' ', '\t']);
+    let marker = MARKERS
+        .iter()
+        .find(|marker| comment.starts_with(**marker))?;
+    let rest = &comment[marker.len()..];
+    // skip an owner like TODO(alice)
+    let rest = match rest.strip_prefix('(') {
+        Some(owned) => &owned[owned.find(')')? + 1..],
+        None => rest,
+    };
+    if !rest.is_empty() && !rest.starts_with([':', ' ', '\t']) {
+        // part of a longer word like TODOS
+        return None;
+    }
+    let mut text = rest.trim_start_matches(':').trim();
+    for end in COMMENT_ENDS {
+        text = text.trim_end_matches(end).trim_end();
+    }
+    match text.is_empty() {
+        true => Some(marker.to_string()),
+        false => Some(format!("{}: {}", marker, text)),
+    }
+}
+
+/// Returns `MARKER: text` when a comment in `line` starts with a marker,
+/// `// TODO(alice): text` and `# FIXME text` are both recognized. Every
+/// comment start is tried since `#` or `;` may also appear in code.
+fn parse_comment(line: &str) -> Option<String> {
+    let trimmed = line.trim_start();
+    // continuation lines of block comments
+    if trimmed.starts_with('*') {
+        return parse_marker(trimmed);
+    }
+    let mut starts: Vec<usize> = COMMENT_STARTS
+        .iter()
+        .flat_map(|start| line.match_indices(start).map(|(index, _)| index))
+        .collect();
+    starts.sort();
+    starts
+        .into_iter()
+        .find_map(|index| parse_marker(&line[index..]))
+}
+
+/// Finds the marked comments in the files below `root`, skipping files
+/// ignored by `.gitignore` and hidden files.
+pub fn find_comments(root: &std::path::Path) -> Result<Vec<Comment>> {
+    let mut comments = Vec::new();
+    let walker = ignore::WalkBuilder::new(root).require_git(false).build();
+    for entry in walker {
+        let entry = entry.context(WalkSnafu {
+            path: root.to_string_lossy(),
+        })?;
+        if !entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_file())
+        {
+            continue;
+        }
+        // binary or non UTF-8 files have no comments worth importing
+        let Ok(content) = std::fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        let path = entry.path().to_string_lossy().to_string();
+        for (index, line) in content.lines().enumerate() {
+            if let Some(text) = parse_comment(line) {
+                comments.push(Comment {
+                    path: path.clone(),
+                    line: index + 1,
+                    text,
+                });
+            }
+        }
+    }
+    Ok(comments)
+}
+
+fn source_path(source: &str) -> &str {
+    source.rsplit_once(':').map_or(source, |(path, _)| path)
+}
+
+/// Imports the comments below `root` as tasks of the repository containing
+/// `root`, if any. Comments which already have a task, even a closed one, are
+/// matched by file and text so that moving them around keeps the task. Open
+/// tasks whose comment disappeared are closed.
+pub fn scan_directory(conn: &db::Connection, root: &str) -> Result<ScanReport> {
+    let root = std::fs::canonicalize(root).context(IoSnafu { path: root })?;
+    let comments = find_comments(&root)?;
+    let cases = "scan";
+    db::ensure_table(conn).context(DatabaseSnafu { cases })?;
+    let repo = crate::repo::detect_repository(&root).map(|repo| repo.key());
+    let root = root.to_string_lossy().to_string();
+    let is_below_root = |path: &str| {
+        path == root
+            || path
+                .strip_prefix(&root)
+                .is_some_and(|rest| rest.starts_with(std::path::MAIN_SEPARATOR))
+    };
+    let mut known: Vec<db::Task> = db::list_all_tasks(conn)
+        .context(DatabaseSnafu { cases })?
+        .into_iter()
+        .filter(|task| {
+            task.source
+                .as_deref()
+                .is_some_and(|source| is_below_root(source_path(source)))
+        })
+        .collect();
+
+    let mut report = ScanReport::default();
+    for comment in comments {
+        let position = known.iter().position(|task| {
+            task.task == comment.text
+                && task.source.as_deref().map(source_path) == Some(comment.path.as_str())
+        });
+        match position {
+            Some(position) => {
+                let mut task = known.swap_remove(position);
+                let source = comment.source();
+                if task.source.as_deref() != Some(source.as_str()) {
+                    task.source = Some(source);
+                    db::update_full_task(conn, &task).context(DatabaseSnafu { cases })?;
+                    report.moved += 1;
+                }
+            }
+            None => {
+                let task = db::Task {
+                    create_time: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                    task: comment.text.clone(),
+                    source: Some(comment.source()),
+                    repo: repo.clone(),
+                    ..Default::default()
+                };
+                db::insert_full_task(conn, &task).context(DatabaseSnafu { cases })?;
+                report.added.push(comment);
+            }
+        }
+    }
+    for task in known {
+        if task.status == db::TaskStatus::Open {
+            db::done_task(conn, task.id).context(DatabaseSnafu { cases })?;
+            report.closed.push(task);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_comment() {
+        let cases = [
+            ("    // TODO: handle errors", Some("TODO: handle errors")),
+            ("x = 1  # FIXME(bob) off by one", Some("FIXME: off by one")),
+            (
+                "/* HACK: until upstream fixes it */",
+                Some("HACK: until upstream fixes it"),
+            ),
+            (
+                " * TODO split this function",
+                Some("TODO: split this function"),
+            ),
+            ("-- TODO", Some("TODO")),
+            ("<!-- TODO: translate -->", Some("TODO: translate")),
+            ("// not a TODO", None),
+            ("// TODOS are nice", None),
+            ("let todo = \"TODO\";", None),
+            ("s = \"#{}\" // TODO: format", Some("TODO: format")),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse_comment(line).as_deref(), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_rescan() {
+        let root = std::env::temp_dir().join(format!("todo-scan-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target\n").unwrap();
+        std::fs::write(root.join("target/out.rs"), "// TODO: ignored\n").unwrap();
+        let file = root.join("main.rs");
+        std::fs::write(&file, "// TODO: first\nfn main() {} // FIXME: second\n").unwrap();
+        let root_str = root.to_str().unwrap();
+
+        let conn = db::memory_connection();
+        let report = scan_directory(&conn, root_str).unwrap();
+        assert_eq!(report.added.len(), 2);
+
+        std::fs::write(&file, "\n\n// FIXME: second\n").unwrap();
+        let report = scan_directory(&conn, root_str).unwrap();
+        assert!(report.added.is_empty());
+        assert_eq!(report.moved, 1);
+        assert_eq!(report.closed.len(), 1);
+        assert_eq!(report.closed[0].task, "TODO: first");
+
+        let tasks = db::list_tasks(&conn).unwrap();
+        assert_eq!(tasks.len(), 1);
+        let task = db::get_task(&conn, tasks[0].id).unwrap().unwrap();
+        assert!(task.source.unwrap().ends_with("main.rs:3"));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,621 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use snafu::ResultExt;
+
+use crate::db::{self, SqlSnafu};
+
+/// Modification time assigned to fields which were never changed after the
+/// row has been inserted, any real modification wins over it.
+const EPOCH: &str = "1970-01-01T00:00:00.000Z";
+
+macro_rules! now_sql {
+    () => {
+        "STRFTIME('%Y-%m-%dT%H:%M:%fZ', 'now')"
+    };
+}
+
+pub(crate) struct SyncField {
+    pub name: &'static str,
+    update_sql: &'static str,
+    trigger_sql: &'static str,
+}
+
+macro_rules! sync_field {
+    ($name:literal) => {
+        SyncField {
+            name: $name,
+            update_sql: concat!("UPDATE todo SET ", $name, " = ?2 WHERE uuid = ?1"),
+            trigger_sql: concat!(
+                "CREATE TRIGGER IF NOT EXISTS todo_clock_",
+                $name,
+                " AFTER UPDATE OF ",
+                $name,
+                " ON todo WHEN OLD.",
+                $name,
+                " IS NOT NEW.",
+                $name,
+                " BEGIN INSERT OR REPLACE INTO todo_clock (uuid, field, mtime) VALUES (NEW.uuid, '",
+                $name,
+                "', ",
+                now_sql!(),
+                "); END"
+            ),
+        }
+    };
+}
+
+/// Columns of `todo` merged field by field with last-writer-wins.
+pub(crate) const SYNC_FIELDS: &[SyncField] = &[
+    sync_field!("create_time"),
+    sync_field!("finished_time"),
+    sync_field!("task"),
+    sync_field!("status"),
+    sync_field!("priority"),
+    sync_field!("project"),
+    sync_field!("tags"),
+    sync_field!("due_time"),
+    sync_field!("source"),
+    sync_field!("repo"),
+];
+
+/// Creates the bookkeeping tables used by the sync engine. `todo_clock`
+/// records when each field of a task was last modified and `todo_tombstone`
+/// remembers tasks which have been removed from the database.
+pub(crate) fn create_sync_tables(conn: &db::Connection) -> db::Result<()> {
+    let sql = r##"
+    CREATE TABLE IF NOT EXISTS todo_clock
+    (
+        uuid TEXT NOT NULL,
+        field TEXT NOT NULL,
+        mtime TEXT NOT NULL,
+        PRIMARY KEY (uuid, field)
+    )
+    "##;
+    conn.execute(sql, []).context(SqlSnafu { sql })?;
+    let sql = r##"
+    CREATE TABLE IF NOT EXISTS todo_tombstone
+    (
+        uuid TEXT PRIMARY KEY,
+        delete_time TEXT NOT NULL
+    )
+    "##;
+    conn.execute(sql, []).context(SqlSnafu { sql })?;
+    let sql = concat!(
+        r##"
+    CREATE TRIGGER IF NOT EXISTS todo_clock_insert AFTER INSERT ON todo
+    BEGIN
+        INSERT OR REPLACE INTO todo_clock (uuid, field, mtime) VALUES (NEW.uuid, '', "##,
+        now_sql!(),
+        r##");
+    END
+    "##
+    );
+    conn.execute(sql, []).context(SqlSnafu { sql })?;
+    let sql = concat!(
+        r##"
+    CREATE TRIGGER IF NOT EXISTS todo_tombstone_delete AFTER DELETE ON todo
+    BEGIN
+        DELETE FROM todo_clock WHERE uuid = OLD.uuid;
+        INSERT OR REPLACE INTO todo_tombstone (uuid, delete_time) VALUES (OLD.uuid, "##,
+        now_sql!(),
+        r##");
+    END
+    "##
+    );
+    conn.execute(sql, []).context(SqlSnafu { sql })?;
+    Ok(())
+}
+
+/// Creates the tables used to sync with a server. Clients keep their cursors
+/// in `todo_sync_state`, the server journals changed tasks in `todo_sync_log`.
+pub(crate) fn create_sync_state_tables(conn: &db::Connection) -> db::Result<()> {
+    let sql = r##"
+    CREATE TABLE IF NOT EXISTS todo_sync_state
+    (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    )
+    "##;
+    conn.execute(sql, []).context(SqlSnafu { sql })?;
+    let sql = r##"
+    CREATE TABLE IF NOT EXISTS todo_sync_log
+    (
+        uuid TEXT PRIMARY KEY,
+        seq INTEGER NOT NULL
+    )
+    "##;
+    conn.execute(sql, []).context(SqlSnafu { sql })?;
+    Ok(())
+}
+
+/// Keeps one trigger per synced column which stamps `todo_clock` whenever the
+/// column changes, so the regular task API does not need to know about sync.
+pub(crate) fn ensure_clock_triggers(conn: &db::Connection) -> db::Result<()> {
+    for field in SYNC_FIELDS {
+        let sql = field.trigger_sql;
+        conn.execute(sql, []).context(SqlSnafu { sql })?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FieldState {
+    pub value: serde_json::Value,
+    pub mtime: String,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RowState {
+    pub uuid: String,
+    /// when the row was inserted into the database it was read from
+    pub ctime: String,
+    pub fields: BTreeMap<String, FieldState>,
+}
+
+impl RowState {
+    /// Latest modification of the row, including its insertion.
+    pub fn mtime(&self) -> &str {
+        self.fields
+            .values()
+            .map(|field| field.mtime.as_str())
+            .chain(std::iter::once(self.ctime.as_str()))
+            .max()
+            .unwrap_or(EPOCH)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Tombstone {
+    pub uuid: String,
+    pub delete_time: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ChangeSet {
+    pub rows: Vec<RowState>,
+    pub tombstones: Vec<Tombstone>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MergeReport {
+    pub inserted: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    /// uuids of every task or tombstone changed by the merge
+    pub changed: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SyncReport {
+    pub local: MergeReport,
+    pub remote: MergeReport,
+}
+
+fn to_json(value: rusqlite::types::Value) -> serde_json::Value {
+    match value {
+        rusqlite::types::Value::Null => serde_json::Value::Null,
+        rusqlite::types::Value::Integer(i) => serde_json::json!(i),
+        rusqlite::types::Value::Real(f) => serde_json::json!(f),
+        rusqlite::types::Value::Text(s) => serde_json::json!(s),
+        rusqlite::types::Value::Blob(b) => serde_json::json!(b),
+    }
+}
+
+fn from_json(value: &serde_json::Value) -> rusqlite::types::Value {
+    match value {
+        serde_json::Value::Null => rusqlite::types::Value::Null,
+        serde_json::Value::Bool(b) => rusqlite::types::Value::Integer(*b as i64),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => rusqlite::types::Value::Integer(i),
+            None => rusqlite::types::Value::Real(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => rusqlite::types::Value::Text(s.clone()),
+        other => rusqlite::types::Value::Text(other.to_string()),
+    }
+}
+
+fn read_clocks(conn: &db::Connection) -> db::Result<BTreeMap<(String, String), String>> {
+    let sql = "SELECT uuid, field, mtime FROM todo_clock";
+    (|| -> rusqlite::Result<BTreeMap<(String, String), String>> {
+        let mut stmt = conn.raw().prepare(sql)?;
+        stmt.query_map([], |row| Ok(((row.get(0)?, row.get(1)?), row.get(2)?)))?
+            .collect()
+    })()
+    .context(SqlSnafu { sql })
+}
+
+fn read_rows(conn: &db::Connection) -> db::Result<BTreeMap<String, RowState>> {
+    let mut clocks = read_clocks(conn)?;
+    let sql = "SELECT * FROM todo";
+    let rows = (|| -> rusqlite::Result<Vec<(String, Vec<rusqlite::types::Value>)>> {
+        let mut stmt = conn.raw().prepare(sql)?;
+        stmt.query_map([], |row| {
+            let values = SYNC_FIELDS
+                .iter()
+                .map(|field| row.get(field.name))
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok((row.get("uuid")?, values))
+        })?
+        .collect()
+    })()
+    .context(SqlSnafu { sql })?;
+
+    let mut take_clock = |uuid: &str, field: &str| {
+        clocks
+            .remove(&(uuid.to_owned(), field.to_owned()))
+            .unwrap_or_else(|| EPOCH.to_owned())
+    };
+    Ok(rows
+        .into_iter()
+        .map(|(uuid, values)| {
+            let fields = SYNC_FIELDS
+                .iter()
+                .zip(values)
+                .map(|(field, value)| {
+                    let state = FieldState {
+                        value: to_json(value),
+                        mtime: take_clock(&uuid, field.name),
+                    };
+                    (field.name.to_owned(), state)
+                })
+                .collect();
+            let row = RowState {
+                ctime: take_clock(&uuid, ""),
+                uuid: uuid.clone(),
+                fields,
+            };
+            (uuid, row)
+        })
+        .collect())
+}
+
+fn read_tombstones(conn: &db::Connection) -> db::Result<Vec<Tombstone>> {
+    let sql = "SELECT uuid, delete_time FROM todo_tombstone";
+    (|| -> rusqlite::Result<Vec<Tombstone>> {
+        let mut stmt = conn.raw().prepare(sql)?;
+        stmt.query_map([], |row| {
+            Ok(Tombstone {
+                uuid: row.get(0)?,
+                delete_time: row.get(1)?,
+            })
+        })?
+        .collect()
+    })()
+    .context(SqlSnafu { sql })
+}
+
+/// Collects every row and tombstone modified after `since`, or everything
+/// when `since` is `None`. Times are compared as produced by `current_time`.
+pub fn collect_changes(conn: &db::Connection, since: Option<&str>) -> db::Result<ChangeSet> {
+    let is_new = |mtime: &str| since.is_none_or(|since| mtime > since);
+    Ok(ChangeSet {
+        rows: read_rows(conn)?
+            .into_values()
+            .filter(|row| is_new(row.mtime()))
+            .collect(),
+        tombstones: read_tombstones(conn)?
+            .into_iter()
+            .filter(|tombstone| is_new(&tombstone.delete_time))
+            .collect(),
+    })
+}
+
+/// Current time in the format used by the sync clocks.
+pub fn current_time(conn: &db::Connection) -> db::Result<String> {
+    let sql = concat!("SELECT ", now_sql!());
+    conn.raw()
+        .query_row(sql, [], |row| row.get(0))
+        .context(SqlSnafu { sql })
+}
+
+/// Decides whether `incoming` should replace `local`. The later modification
+/// wins, ties are broken by comparing the values so that both sides of a sync
+/// pick the same winner.
+fn incoming_wins(local: &FieldState, incoming: &FieldState) -> bool {
+    match incoming.mtime.cmp(&local.mtime) {
+        std::cmp::Ordering::Greater => true,
+        std::cmp::Ordering::Less => false,
+        std::cmp::Ordering::Equal => {
+            let (incoming, local) = (incoming.value.to_string(), local.value.to_string());
+            incoming > local
+        }
+    }
+}
+
+fn write_clock(conn: &db::Connection, uuid: &str, field: &str, mtime: &str) -> db::Result<()> {
+    let sql = "INSERT OR REPLACE INTO todo_clock (uuid, field, mtime) VALUES (?1, ?2, ?3)";
+    conn.execute(sql, [uuid, field, mtime])
+        .context(SqlSnafu { sql })?;
+    Ok(())
+}
+
+fn write_field(
+    conn: &db::Connection,
+    uuid: &str,
+    field: &SyncField,
+    state: &FieldState,
+) -> db::Result<()> {
+    let sql = field.update_sql;
+    conn.execute(sql, rusqlite::params![uuid, from_json(&state.value)])
+        .context(SqlSnafu { sql })?;
+    write_clock(conn, uuid, field.name, &state.mtime)
+}
+
+fn insert_row(conn: &db::Connection, row: &RowState) -> db::Result<()> {
+    let value = |name: &str| {
+        row.fields
+            .get(name)
+            .map(|field| from_json(&field.value))
+            .unwrap_or(rusqlite::types::Value::Null)
+    };
+    let sql = "INSERT INTO todo (uuid, create_time, task) VALUES (?1, ?2, ?3)";
+    conn.execute(
+        sql,
+        rusqlite::params![row.uuid, value("create_time"), value("task")],
+    )
+    .context(SqlSnafu { sql })?;
+    for field in SYNC_FIELDS {
+        if let Some(state) = row.fields.get(field.name) {
+            write_field(conn, &row.uuid, field, state)?;
+        }
+    }
+    write_clock(conn, &row.uuid, "", &row.ctime)
+}
+
+fn merge_row(conn: &db::Connection, local: &RowState, incoming: &RowState) -> db::Result<bool> {
+    let mut changed = false;
+    for field in SYNC_FIELDS {
+        let (Some(local_state), Some(incoming_state)) = (
+            local.fields.get(field.name),
+            incoming.fields.get(field.name),
+        ) else {
+            continue;
+        };
+        if incoming_wins(local_state, incoming_state) {
+            write_field(conn, &local.uuid, field, incoming_state)?;
+            changed = true;
+        }
+    }
+    Ok(changed)
+}
+
+fn apply_tombstone(conn: &db::Connection, tombstone: &Tombstone) -> db::Result<(bool, bool)> {
+    let sql = "SELECT COUNT(*) FROM todo_tombstone WHERE uuid = ?1";
+    let known: i64 = conn
+        .raw()
+        .query_row(sql, [&tombstone.uuid], |row| row.get(0))
+        .context(SqlSnafu { sql })?;
+    let sql = "DELETE FROM todo WHERE uuid = ?1";
+    let deleted = conn
+        .execute(sql, [&tombstone.uuid])
+        .context(SqlSnafu { sql })?;
+    let sql = r##"
+    INSERT INTO todo_tombstone (uuid, delete_time) VALUES (?1, ?2)
+    ON CONFLICT (uuid) DO UPDATE SET delete_time = MIN(delete_time, excluded.delete_time)
+    "##;
+    conn.execute(sql, [&tombstone.uuid, &tombstone.delete_time])
+        .context(SqlSnafu { sql })?;
+    Ok((deleted > 0, known == 0))
+}
+
+/// Merges `changes` into the database. Removed tasks stay removed, for tasks
+/// known on both sides each field keeps its latest modification.
+pub fn apply_changes(conn: &db::Connection, changes: &ChangeSet) -> db::Result<MergeReport> {
+    let sql = "BEGIN";
+    let tx = conn
+        .raw()
+        .unchecked_transaction()
+        .context(SqlSnafu { sql })?;
+    let mut report = MergeReport::default();
+
+    for tombstone in &changes.tombstones {
+        let (deleted, new_tombstone) = apply_tombstone(conn, tombstone)?;
+        if deleted {
+            report.deleted += 1;
+        }
+        if deleted || new_tombstone {
+            report.changed.insert(tombstone.uuid.clone());
+        }
+    }
+    let tombstones: BTreeSet<String> = read_tombstones(conn)?
+        .into_iter()
+        .map(|tombstone| tombstone.uuid)
+        .collect();
+    let local_rows = read_rows(conn)?;
+    for row in &changes.rows {
+        if tombstones.contains(&row.uuid) {
+            continue;
+        }
+        match local_rows.get(&row.uuid) {
+            None => {
+                insert_row(conn, row)?;
+                report.inserted += 1;
+                report.changed.insert(row.uuid.clone());
+            }
+            Some(local) => {
+                if merge_row(conn, local, row)? {
+                    report.updated += 1;
+                    report.changed.insert(row.uuid.clone());
+                }
+            }
+        }
+    }
+
+    let sql = "COMMIT";
+    tx.commit().context(SqlSnafu { sql })?;
+    Ok(report)
+}
+
+/// Records that the tasks in `uuids` changed, so that `changes_since` hands
+/// them out to clients whose cursor is older than this call.
+pub fn journal_changes(conn: &db::Connection, uuids: &BTreeSet<String>) -> db::Result<i64> {
+    let sql = r##"
+    INSERT INTO todo_sync_log (uuid, seq)
+    VALUES (?1, (SELECT COALESCE(MAX(seq), 0) + 1 FROM todo_sync_log))
+    ON CONFLICT (uuid) DO UPDATE SET seq = excluded.seq
+    "##;
+    for uuid in uuids {
+        conn.execute(sql, [uuid]).context(SqlSnafu { sql })?;
+    }
+    latest_cursor(conn)
+}
+
+fn latest_cursor(conn: &db::Connection) -> db::Result<i64> {
+    let sql = "SELECT COALESCE(MAX(seq), 0) FROM todo_sync_log";
+    conn.raw()
+        .query_row(sql, [], |row| row.get(0))
+        .context(SqlSnafu { sql })
+}
+
+/// Returns the current cursor together with every row and tombstone journaled
+/// after `cursor`. A zero cursor returns the whole database.
+pub fn changes_since(conn: &db::Connection, cursor: i64) -> db::Result<(i64, ChangeSet)> {
+    let latest = latest_cursor(conn)?;
+    let changes = collect_changes(conn, None)?;
+    if cursor == 0 {
+        return Ok((latest, changes));
+    }
+    let sql = "SELECT uuid FROM todo_sync_log WHERE seq > ?1";
+    let uuids = (|| -> rusqlite::Result<BTreeSet<String>> {
+        let mut stmt = conn.raw().prepare(sql)?;
+        stmt.query_map([cursor], |row| row.get(0))?.collect()
+    })()
+    .context(SqlSnafu { sql })?;
+    Ok((
+        latest,
+        ChangeSet {
+            rows: changes
+                .rows
+                .into_iter()
+                .filter(|row| uuids.contains(&row.uuid))
+                .collect(),
+            tombstones: changes
+                .tombstones
+                .into_iter()
+                .filter(|tombstone| uuids.contains(&tombstone.uuid))
+                .collect(),
+        },
+    ))
+}
+
+/// Per remote bookkeeping of a client, e.g. the cursor of the last pull.
+pub fn get_sync_state(conn: &db::Connection, key: &str) -> db::Result<Option<String>> {
+    let sql = "SELECT value FROM todo_sync_state WHERE key = ?1";
+    match conn.raw().query_row(sql, [key], |row| row.get(0)) {
+        Ok(value) => Ok(Some(value)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e).context(SqlSnafu { sql }),
+    }
+}
+
+pub fn set_sync_state(conn: &db::Connection, key: &str, value: &str) -> db::Result<()> {
+    let sql = "INSERT OR REPLACE INTO todo_sync_state (key, value) VALUES (?1, ?2)";
+    conn.execute(sql, [key, value]).context(SqlSnafu { sql })?;
+    Ok(())
+}
+
+/// Synchronizes two todo databases so that both end up with the same tasks.
+pub fn sync_databases(local: &db::Connection, remote: &db::Connection) -> db::Result<SyncReport> {
+    db::ensure_table(local)?;
+    db::ensure_table(remote)?;
+    let local_changes = collect_changes(local, None)?;
+    let remote_changes = collect_changes(remote, None)?;
+    Ok(SyncReport {
+        local: apply_changes(local, &remote_changes)?,
+        remote: apply_changes(remote, &local_changes)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDb;
+
+    fn tasks(conn: &db::Connection) -> Vec<(String, String, db::TaskStatus)> {
+        let mut tasks: Vec<_> = db::list_all_tasks(conn)
+            .unwrap()
+            .into_iter()
+            .map(|task| (task.uuid, task.task, task.status))
+            .collect();
+        tasks.sort_by(|a, b| a.0.cmp(&b.0));
+        tasks
+    }
+
+    fn uuid_of(conn: &db::Connection, text: &str) -> (i64, String) {
+        let task = db::list_all_tasks(conn)
+            .unwrap()
+            .into_iter()
+            .find(|task| task.task == text)
+            .unwrap();
+        (task.id, task.uuid)
+    }
+
+    #[test]
+    fn test_sync_new_tasks() {
+        let (a, b) = (TempDb::new("new-a"), TempDb::new("new-b"));
+        let (a, b) = (a.open(), b.open());
+        db::insert_task(&a, "from a").unwrap();
+        db::insert_task(&b, "from b").unwrap();
+
+        let report = sync_databases(&a, &b).unwrap();
+        assert_eq!(report.local.inserted, 1);
+        assert_eq!(report.remote.inserted, 1);
+        assert_eq!(tasks(&a), tasks(&b));
+        assert_eq!(tasks(&a).len(), 2);
+
+        let report = sync_databases(&a, &b).unwrap();
+        assert_eq!(report, SyncReport::default());
+    }
+
+    #[test]
+    fn test_sync_merges_fields() {
+        let (a, b) = (TempDb::new("fields-a"), TempDb::new("fields-b"));
+        let (a, b) = (a.open(), b.open());
+        db::insert_task(&a, "task").unwrap();
+        sync_databases(&a, &b).unwrap();
+
+        let (id_a, uuid) = uuid_of(&a, "task");
+        let (id_b, _) = uuid_of(&b, "task");
+        db::edit_task(&a, id_a, &"edited on a".to_owned()).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        db::done_task(&b, id_b).unwrap();
+
+        sync_databases(&a, &b).unwrap();
+        let expected = vec![(uuid, "edited on a".to_owned(), db::TaskStatus::Closed)];
+        assert_eq!(tasks(&a), expected);
+        assert_eq!(tasks(&b), expected);
+    }
+
+    #[test]
+    fn test_sync_concurrent_edit_is_deterministic() {
+        let (a, b) = (TempDb::new("edit-a"), TempDb::new("edit-b"));
+        let (a, b) = (a.open(), b.open());
+        db::insert_task(&a, "task").unwrap();
+        sync_databases(&a, &b).unwrap();
+
+        db::edit_task(&a, uuid_of(&a, "task").0, &"first".to_owned()).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        db::edit_task(&b, uuid_of(&b, "task").0, &"second".to_owned()).unwrap();
+
+        sync_databases(&a, &b).unwrap();
+        assert_eq!(tasks(&a), tasks(&b));
+        assert_eq!(tasks(&a)[0].1, "second");
+    }
+
+    #[test]
+    fn test_sync_removed_task_wins() {
+        let (a, b) = (TempDb::new("removed-a"), TempDb::new("removed-b"));
+        let (a, b) = (a.open(), b.open());
+        db::insert_task(&a, "task").unwrap();
+        sync_databases(&a, &b).unwrap();
+
+        let sql = "DELETE FROM todo";
+        a.execute(sql, []).unwrap();
+        db::edit_task(&b, uuid_of(&b, "task").0, &"edited".to_owned()).unwrap();
+
+        let report = sync_databases(&a, &b).unwrap();
+        assert_eq!(report.remote.deleted, 1);
+        assert!(tasks(&a).is_empty());
+        assert!(tasks(&b).is_empty());
+    }
+}
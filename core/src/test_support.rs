@@ -0,0 +1,33 @@
+//! Helpers for the tests of this crate and of the crates using it, enabled
+//! for them with the 'test-support' feature.
+
+use crate::db;
+
+/// A database file in the temp dir, removed when dropped.
+pub struct TempDb(String);
+
+impl TempDb {
+    pub fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("todo-test-{}-{}.db", name, std::process::id()));
+        let path = path.to_str().unwrap().to_owned();
+        let _ = std::fs::remove_file(&path);
+        TempDb(path)
+    }
+
+    pub fn path(&self) -> &str {
+        &self.0
+    }
+
+    pub fn open(&self) -> db::Connection {
+        let conn = db::open_connection(&self.0).unwrap();
+        db::ensure_table(&conn).unwrap();
+        conn
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
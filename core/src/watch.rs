@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::db;
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ChangeEvent {
+    Inserted { id: i64 },
+    Updated { id: i64 },
+    Removed { id: i64 },
+}
+
+type Snapshot = BTreeMap<i64, db::Task>;
+
+fn take_snapshot(conn: &db::Connection) -> db::Result<Snapshot> {
+    Ok(db::list_all_tasks(conn)?
+        .into_iter()
+        .map(|task| (task.id, task))
+        .collect())
+}
+
+fn diff_snapshot(old: &Snapshot, new: &Snapshot) -> Vec<ChangeEvent> {
+    let mut events = Vec::new();
+    for (id, task) in new {
+        match old.get(id) {
+            None => events.push(ChangeEvent::Inserted { id: *id }),
+            Some(old_task) if old_task != task => events.push(ChangeEvent::Updated { id: *id }),
+            Some(_) => {}
+        }
+    }
+    for id in old.keys() {
+        if !new.contains_key(id) {
+            events.push(ChangeEvent::Removed { id: *id });
+        }
+    }
+    events
+}
+
+/// Watches the todo database for changes made by any connection, including
+/// other processes such as the CLI or the MCP server. The background thread
+/// stops when the watcher is dropped.
+pub struct Watcher {
+    stop: Arc<AtomicBool>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Polls `PRAGMA data_version` every `interval` and calls `on_change` with the
+/// task level differences whenever another connection committed a change.
+pub fn watch<F>(interval: std::time::Duration, on_change: F) -> db::Result<Watcher>
+where
+    F: FnMut(Vec<ChangeEvent>) + Send + 'static,
+{
+    watch_connection(db::create_connection()?, interval, on_change)
+}
+
+pub fn watch_connection<F>(
+    conn: db::Connection,
+    interval: std::time::Duration,
+    mut on_change: F,
+) -> db::Result<Watcher>
+where
+    F: FnMut(Vec<ChangeEvent>) + Send + 'static,
+{
+    db::ensure_table(&conn)?;
+    let mut version = db::data_version(&conn)?;
+    let mut snapshot = take_snapshot(&conn)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+    let handle = std::thread::spawn(move || {
+        while !thread_stop.load(Ordering::Relaxed) {
+            std::thread::sleep(interval);
+            let new_version = match db::data_version(&conn) {
+                Ok(new_version) => new_version,
+                Err(_) => continue,
+            };
+            if new_version == version {
+                continue;
+            }
+            // a failed snapshot is retried on the next poll
+            let new_snapshot = match take_snapshot(&conn) {
+                Ok(new_snapshot) => new_snapshot,
+                Err(_) => continue,
+            };
+            version = new_version;
+            let events = diff_snapshot(&snapshot, &new_snapshot);
+            snapshot = new_snapshot;
+            if !events.is_empty() {
+                on_change(events);
+            }
+        }
+    });
+    Ok(Watcher {
+        stop,
+        handle: Some(handle),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: i64, text: &str) -> db::Task {
+        db::Task {
+            id,
+            uuid: format!("uuid-{}", id),
+            create_time: "2025-01-01 00:00:00".to_owned(),
+            finished_time: None,
+            task: text.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff_snapshot() {
+        let old: Snapshot = [(1, task(1, "a")), (2, task(2, "b")), (3, task(3, "c"))].into();
+        let new: Snapshot = [(1, task(1, "a")), (2, task(2, "B")), (4, task(4, "d"))].into();
+        assert_eq!(
+            diff_snapshot(&old, &new),
+            vec![
+                ChangeEvent::Updated { id: 2 },
+                ChangeEvent::Inserted { id: 4 },
+                ChangeEvent::Removed { id: 3 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_watch_other_connection() {
+        let temp = crate::test_support::TempDb::new("watch");
+        let (tx, rx) = std::sync::mpsc::channel();
+        let _watcher = watch_connection(
+            temp.open(),
+            std::time::Duration::from_millis(10),
+            move |events| tx.send(events).unwrap(),
+        )
+        .unwrap();
+
+        db::insert_task(&temp.open(), "task").unwrap();
+        let events = rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+        assert!(matches!(events[..], [ChangeEvent::Inserted { .. }]));
+    }
+}
@@ -1,17 +1,21 @@
 #![allow(dead_code)]
 
 use rmcp::{ServerHandler, ServiceExt, tool, transport};
-use todo_core::db;
+use todo_core::{db, repo};
+
+const TASKS_URI: &str = "todo://tasks";
 
 #[derive(Clone)]
 struct TodoService {
     tool_router: rmcp::handler::server::tool::ToolRouter<TodoService>,
+    subscriptions: std::sync::Arc<std::sync::Mutex<std::collections::BTreeSet<String>>>,
 }
 
 impl TodoService {
     fn new() -> Self {
         Self {
             tool_router: Self::tool_router(),
+            subscriptions: Default::default(),
         }
     }
 }
@@ -26,6 +30,12 @@ fn convert_err(e: db::DBError) -> rmcp::ErrorData {
     )
 }
 
+/// Tasks are scoped to the git repository of the working directory the
+/// server was started in, like the command line.
+fn current_repo() -> Option<String> {
+    repo::current_repository().map(|repo| repo.key())
+}
+
 #[derive(Debug, serde::Deserialize, rmcp::schemars::JsonSchema)]
 pub struct StructRequest {
     pub task_name: String,
@@ -40,14 +50,14 @@ impl TodoService {
     ) -> ToolResult {
         let conn = db::create_connection().map_err(convert_err)?;
         db::ensure_table(&conn).map_err(convert_err)?;
-        db::insert_task(&conn, &task_name).map_err(convert_err)?;
+        db::insert_task_in(&conn, &task_name, current_repo().as_deref()).map_err(convert_err)?;
         Ok(rmcp::model::CallToolResult::success(vec![]))
     }
     #[tool(description = "list all pending todo task")]
     fn list_tasks(&self) -> ToolResult {
         let conn = db::create_connection().map_err(convert_err)?;
         db::ensure_table(&conn).map_err(convert_err)?;
-        let tasks = db::list_tasks(&conn).map_err(convert_err)?;
+        let tasks = db::list_tasks_in(&conn, current_repo().as_deref()).map_err(convert_err)?;
         let ret = tasks
             .iter()
             .map(|task| {
@@ -61,6 +71,28 @@ impl TodoService {
     }
 }
 
+fn read_tasks_resource() -> Result<rmcp::model::ReadResourceResult, rmcp::ErrorData> {
+    let conn = db::create_connection().map_err(convert_err)?;
+    db::ensure_table(&conn).map_err(convert_err)?;
+    let tasks = db::list_tasks_in(&conn, current_repo().as_deref()).map_err(convert_err)?;
+    let text = serde_json::to_string(&tasks)
+        .map_err(|e| rmcp::ErrorData::internal_error(e.to_string(), None))?;
+    Ok(rmcp::model::ReadResourceResult {
+        contents: vec![rmcp::model::ResourceContents::text(text, TASKS_URI)],
+    })
+}
+
+fn check_resource_uri(uri: &str) -> Result<(), rmcp::ErrorData> {
+    if uri == TASKS_URI {
+        Ok(())
+    } else {
+        Err(rmcp::ErrorData::resource_not_found(
+            format!("unknown resource '{}'", uri),
+            None,
+        ))
+    }
+}
+
 #[rmcp::tool_handler]
 impl ServerHandler for TodoService {
     fn get_info(&self) -> rmcp::model::ServerInfo {
@@ -68,17 +100,79 @@ impl ServerHandler for TodoService {
             instructions: Some("A tool to manage pending todo task".into()),
             capabilities: rmcp::model::ServerCapabilities::builder()
                 .enable_tools()
+                .enable_resources()
+                .enable_resources_subscribe()
                 .build(),
             ..Default::default()
         }
     }
+
+    async fn list_resources(
+        &self,
+        _request: Option<rmcp::model::PaginatedRequestParam>,
+        _context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<rmcp::model::ListResourcesResult, rmcp::ErrorData> {
+        let mut resource = rmcp::model::RawResource::new(TASKS_URI, "pending tasks");
+        resource.mime_type = Some("application/json".into());
+        Ok(rmcp::model::ListResourcesResult::with_all_items(vec![
+            rmcp::model::AnnotateAble::no_annotation(resource),
+        ]))
+    }
+
+    async fn read_resource(
+        &self,
+        rmcp::model::ReadResourceRequestParam { uri }: rmcp::model::ReadResourceRequestParam,
+        _context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<rmcp::model::ReadResourceResult, rmcp::ErrorData> {
+        check_resource_uri(&uri)?;
+        read_tasks_resource()
+    }
+
+    async fn subscribe(
+        &self,
+        rmcp::model::SubscribeRequestParam { uri }: rmcp::model::SubscribeRequestParam,
+        _context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<(), rmcp::ErrorData> {
+        check_resource_uri(&uri)?;
+        self.subscriptions.lock().unwrap().insert(uri);
+        Ok(())
+    }
+
+    async fn unsubscribe(
+        &self,
+        rmcp::model::UnsubscribeRequestParam { uri }: rmcp::model::UnsubscribeRequestParam,
+        _context: rmcp::service::RequestContext<rmcp::RoleServer>,
+    ) -> Result<(), rmcp::ErrorData> {
+        self.subscriptions.lock().unwrap().remove(&uri);
+        Ok(())
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    let service = TodoService::new()
+    let service = TodoService::new();
+    let subscriptions = service.subscriptions.clone();
+    let service = service
         .serve(transport::stdio())
         .await
         .expect("create service failed");
+
+    let peer = service.peer().clone();
+    let runtime = tokio::runtime::Handle::current();
+    let _watcher = todo_core::watch::watch(std::time::Duration::from_millis(500), move |_| {
+        if !subscriptions.lock().unwrap().contains(TASKS_URI) {
+            return;
+        }
+        let peer = peer.clone();
+        runtime.spawn(async move {
+            let param = rmcp::model::ResourceUpdatedNotificationParam {
+                uri: TASKS_URI.into(),
+            };
+            if let Err(e) = peer.notify_resource_updated(param).await {
+                eprintln!("failed to notify resource updated: {}", e);
+            }
+        });
+    })
+    .expect("watch database failed");
     service.waiting().await.expect("service failed");
 }
@@ -0,0 +1,126 @@
+use std::io::Read;
+
+use snafu::{ResultExt, prelude::Snafu};
+use todo_core::{db, remote, sync};
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum ServerError {
+    #[snafu(display("failed to create todo root directory"))]
+    Root { source: todo_core::root_path::Error },
+    #[snafu(display("failed to listen on '{}'", addr))]
+    Bind {
+        source: std::io::Error,
+        addr: String,
+    },
+    #[snafu(display("database error when {}", cases))]
+    Database {
+        source: db::DBError,
+        cases: &'static str,
+    },
+    #[snafu(display("invalid request body"))]
+    Body { source: std::io::Error },
+    #[snafu(display("request body is larger than {} bytes", limit))]
+    BodyTooLarge { limit: usize },
+    #[snafu(display("invalid change set"))]
+    ChangeSet { source: serde_json::Error },
+}
+
+pub type Result<T> = std::result::Result<T, ServerError>;
+
+/// Largest change set accepted by `POST /push`.
+pub const MAX_PUSH_BYTES: usize = 32 * 1024 * 1024;
+
+/// A sync server keeping the merged tasks of all clients in its own todo
+/// database. Clients push their changes to `POST /push` and fetch the changes
+/// of other clients from `GET /pull?since=<cursor>`.
+pub struct SyncServer {
+    http: tiny_http::Server,
+    conn: db::Connection,
+}
+
+impl SyncServer {
+    pub fn bind(addr: &str, db_path: &str) -> Result<Self> {
+        let http = tiny_http::Server::http(addr)
+            .map_err(std::io::Error::other)
+            .context(BindSnafu { addr })?;
+        let conn = db::open_connection(db_path).context(DatabaseSnafu {
+            cases: "open database",
+        })?;
+        db::ensure_table(&conn).context(DatabaseSnafu {
+            cases: "open database",
+        })?;
+        Ok(Self { http, conn })
+    }
+
+    pub fn local_addr(&self) -> Option<std::net::SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Serves requests one by one until the process exits.
+    pub fn run(&self) {
+        for request in self.http.incoming_requests() {
+            self.handle(request);
+        }
+    }
+
+    fn handle(&self, mut request: tiny_http::Request) {
+        let url = request.url().to_owned();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        let response = match (request.method(), path) {
+            (tiny_http::Method::Get, "/pull") => self.pull(query).map(|body| (200, body)),
+            (tiny_http::Method::Post, "/push") => self.push(&mut request).map(|body| (200, body)),
+            _ => Ok((404, serde_json::json!({ "error": "not found" }))),
+        };
+        let (status, body) = response.unwrap_or_else(|e| {
+            let status = match e {
+                ServerError::Body { .. } | ServerError::ChangeSet { .. } => 400,
+                ServerError::BodyTooLarge { .. } => 413,
+                _ => 500,
+            };
+            let report = snafu::Report::from_error(&e).to_string();
+            (status, serde_json::json!({ "error": report }))
+        });
+        let header = tiny_http::Header::from_bytes("Content-Type", "application/json").unwrap();
+        let response = tiny_http::Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(header);
+        if let Err(e) = request.respond(response) {
+            eprintln!("failed to respond to '{}': {}", url, e);
+        }
+    }
+
+    fn pull(&self, query: &str) -> Result<serde_json::Value> {
+        let since = query
+            .split('&')
+            .filter_map(|pair| pair.strip_prefix("since="))
+            .find_map(|since| since.parse::<i64>().ok())
+            .unwrap_or(0);
+        let (cursor, changes) =
+            sync::changes_since(&self.conn, since).context(DatabaseSnafu { cases: "pull" })?;
+        Ok(serde_json::json!(remote::PullResponse { cursor, changes }))
+    }
+
+    fn push(&self, request: &mut tiny_http::Request) -> Result<serde_json::Value> {
+        let limit = MAX_PUSH_BYTES;
+        if request.body_length().is_some_and(|length| length > limit) {
+            return BodyTooLargeSnafu { limit }.fail();
+        }
+        // the length may be missing with a chunked body
+        let mut body = String::new();
+        request
+            .as_reader()
+            .take(limit as u64 + 1)
+            .read_to_string(&mut body)
+            .context(BodySnafu {})?;
+        if body.len() > limit {
+            return BodyTooLargeSnafu { limit }.fail();
+        }
+        let changes: sync::ChangeSet = serde_json::from_str(&body).context(ChangeSetSnafu {})?;
+        let report =
+            sync::apply_changes(&self.conn, &changes).context(DatabaseSnafu { cases: "push" })?;
+        sync::journal_changes(&self.conn, &report.changed)
+            .context(DatabaseSnafu { cases: "push" })?;
+        Ok(serde_json::json!(report))
+    }
+}
@@ -0,0 +1,38 @@
+use clap::Parser;
+use snafu::ResultExt;
+use todo_server::{RootSnafu, ServerError};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "todo sync server")]
+struct Cli {
+    /// address to listen on
+    #[arg(long, default_value = "127.0.0.1:8765")]
+    addr: String,
+    /// database keeping the merged tasks, defaults to 'server.db' in the todo folder
+    #[arg(long)]
+    db: Option<String>,
+}
+
+fn server_main() -> Result<(), ServerError> {
+    let cli = Cli::parse();
+    let db_path = match cli.db {
+        Some(db_path) => db_path,
+        None => format!(
+            "{}/server.db",
+            todo_core::root_path::get_folder().context(RootSnafu {})?
+        ),
+    };
+    let server = todo_server::SyncServer::bind(&cli.addr, &db_path)?;
+    if let Some(addr) = server.local_addr() {
+        println!("todo sync server listening on http://{}", addr);
+    }
+    server.run();
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = server_main() {
+        eprintln!("Error stack:\n{}", snafu::Report::from_error(&e));
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,107 @@
+use todo_core::test_support::TempDb;
+use todo_core::{db, remote};
+
+fn task_texts(conn: &db::Connection) -> Vec<(String, db::TaskStatus)> {
+    let mut tasks: Vec<_> = db::list_all_tasks(conn)
+        .unwrap()
+        .into_iter()
+        .map(|task| (task.task, task.status))
+        .collect();
+    tasks.sort_by(|a, b| a.0.cmp(&b.0));
+    tasks
+}
+
+#[test]
+fn test_sync_two_clients_through_server() {
+    let server_db = TempDb::new("server");
+    let server = todo_server::SyncServer::bind("127.0.0.1:0", server_db.path()).unwrap();
+    let url = format!("http://{}", server.local_addr().unwrap());
+    std::thread::spawn(move || server.run());
+
+    let (a_db, b_db) = (TempDb::new("a"), TempDb::new("b"));
+    let (a, b) = (a_db.open(), b_db.open());
+
+    db::insert_task(&a, "from a").unwrap();
+    let report = remote::sync_with_server(&a, &url).unwrap();
+    assert_eq!(report.remote.inserted, 1);
+
+    db::insert_task(&b, "from b").unwrap();
+    let report = remote::sync_with_server(&b, &url).unwrap();
+    assert_eq!(report.local.inserted, 1);
+    assert_eq!(report.remote.inserted, 1);
+
+    let id = db::list_all_tasks(&b)
+        .unwrap()
+        .into_iter()
+        .find(|task| task.task == "from a")
+        .unwrap()
+        .id;
+    db::done_task(&b, id).unwrap();
+    remote::sync_with_server(&b, &url).unwrap();
+
+    let report = remote::sync_with_server(&a, &url).unwrap();
+    assert_eq!(report.local.inserted, 1);
+    assert_eq!(report.local.updated, 1);
+    assert_eq!(task_texts(&a), task_texts(&b));
+    assert_eq!(
+        task_texts(&a),
+        vec![
+            ("from a".to_owned(), db::TaskStatus::Closed),
+            ("from b".to_owned(), db::TaskStatus::Open),
+        ]
+    );
+
+    let report = remote::sync_with_server(&a, &url).unwrap();
+    assert!(report.local.changed.is_empty());
+    assert!(report.remote.changed.is_empty());
+}
+
+#[test]
+fn test_push_body_too_large() {
+    use std::io::{Read, Write};
+
+    let server_db = TempDb::new("server-limit");
+    let server = todo_server::SyncServer::bind("127.0.0.1:0", server_db.path()).unwrap();
+    let addr = server.local_addr().unwrap();
+    std::thread::spawn(move || server.run());
+
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "POST /push HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        addr,
+        todo_server::MAX_PUSH_BYTES + 1
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+}
+
+#[test]
+fn test_sync_with_peer_clock_ahead() {
+    let server_db = TempDb::new("server-skew");
+    let server = todo_server::SyncServer::bind("127.0.0.1:0", server_db.path()).unwrap();
+    let url = format!("http://{}", server.local_addr().unwrap());
+    std::thread::spawn(move || server.run());
+
+    let (a_db, b_db) = (TempDb::new("skew-a"), TempDb::new("skew-b"));
+    let (a, b) = (a_db.open(), b_db.open());
+
+    // b's clock runs years ahead of a's
+    db::insert_task(&b, "from b").unwrap();
+    b.execute(
+        "UPDATE todo_clock SET mtime = '2999-01-01T00:00:00.000Z'",
+        [],
+    )
+    .unwrap();
+    remote::sync_with_server(&b, &url).unwrap();
+    let report = remote::sync_with_server(&a, &url).unwrap();
+    assert_eq!(report.local.inserted, 1);
+
+    db::insert_task(&a, "from a").unwrap();
+    let report = remote::sync_with_server(&a, &url).unwrap();
+    assert_eq!(report.remote.inserted, 1);
+    remote::sync_with_server(&b, &url).unwrap();
+    assert_eq!(task_texts(&a), task_texts(&b));
+}